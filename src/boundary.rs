@@ -0,0 +1,69 @@
+use crate::config::*;
+
+fn max_coordinate() -> f32 {
+    (GAME_SIZE as f32).next_down()
+}
+
+pub fn displacement(from: f32, to: f32) -> f32 {
+    let delta = from - to;
+    match BOUNDARY_MODE {
+        BoundaryMode::Wrap => {
+            let size = GAME_SIZE as f32;
+            if delta > size / 2.0 {
+                delta - size
+            } else if delta < -size / 2.0 {
+                delta + size
+            } else {
+                delta
+            }
+        }
+        _ => delta,
+    }
+}
+
+pub fn distance_sq(x1: f32, y1: f32, x2: f32, y2: f32) -> f32 {
+    displacement(x1, x2).powi(2) + displacement(y1, y2).powi(2)
+}
+
+/// Applies the boundary mode to a move from `position` to `next_position`,
+/// returning the resulting position and adjusting `velocity` to match.
+pub fn apply_boundary(position: f32, next_position: f32, velocity: &mut f32) -> f32 {
+    if (0.0..GAME_SIZE as f32).contains(&next_position) {
+        return next_position;
+    }
+
+    match BOUNDARY_MODE {
+        BoundaryMode::Cancel => {
+            *velocity = 0.0;
+            position
+        }
+        BoundaryMode::Wrap => confine(next_position),
+        BoundaryMode::Reflect => {
+            *velocity = -*velocity;
+            let reflected = if next_position < 0.0 {
+                -next_position
+            } else {
+                2.0 * GAME_SIZE as f32 - next_position
+            };
+            reflected.clamp(0.0, max_coordinate())
+        }
+        BoundaryMode::Clamp => {
+            *velocity = 0.0;
+            next_position.clamp(0.0, max_coordinate())
+        }
+    }
+}
+
+pub fn confine(position: f32) -> f32 {
+    match BOUNDARY_MODE {
+        BoundaryMode::Wrap => {
+            let wrapped = position.rem_euclid(GAME_SIZE as f32);
+            if wrapped >= GAME_SIZE as f32 {
+                0.0
+            } else {
+                wrapped
+            }
+        }
+        _ => position.clamp(0.0, max_coordinate()),
+    }
+}
@@ -2,8 +2,22 @@ use rand::{thread_rng, Rng};
 
 use rustc_hash::FxHashMap;
 
+use crate::boundary::{apply_boundary, confine, displacement};
+use crate::chemical_field::DepositRecord;
+use crate::codons::Phenotype;
 use crate::config::*;
-use crate::dna::DNA;
+use crate::dna::{CodonExpression, DNA};
+
+fn get_force_falloff(distance_sq: f32) -> f32 {
+    let softened_distance_sq = distance_sq + FORCE_SOFTENING * FORCE_SOFTENING;
+    match FORCE_LAW {
+        ForceLaw::InverseSquare => 1.0 / softened_distance_sq,
+        ForceLaw::InverseLinear => 1.0 / softened_distance_sq.sqrt(),
+        ForceLaw::Gaussian => (-distance_sq / (2.0 * FORCE_LAW_SCALE * FORCE_LAW_SCALE)).exp(),
+        ForceLaw::LinearToCutoff => (1.0 - distance_sq.sqrt() / FORCE_LAW_SCALE).max(0.0),
+        ForceLaw::Constant => 1.0,
+    }
+}
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct ReadableCell {
@@ -11,46 +25,24 @@ pub struct ReadableCell {
     y: f32,
     food: f32,
     dna: DNA,
-    attractions: FxHashMap<u16, f32>,
-    emissions: Vec<(u16, f32)>,
+    #[serde(flatten)]
+    phenotype: Phenotype,
     disabled_codons: Vec<(usize, bool)>,
-    global_mutation_rate: f32,
-    individual_mutation_rates: FxHashMap<usize, f32>,
-    primary_mutation_rate: f32,
-    secondary_mutation_rate: f32,
-    add_codon_mutation_rate: f32,
-    remove_codon_mutation_rate: f32,
-    food_to_replicate: f32,
-    size: f32,
+    codon_expression: Vec<CodonExpression>,
+    epigenetic_marks: FxHashMap<u16, f32>,
 }
 
 impl ReadableCell {
     pub fn new(cell: &Cell) -> Self {
-        let (
-            global_mutation_rate,
-            individual_mutation_rates,
-            primary_mutation_rate,
-            secondary_mutation_rate,
-            add_codon_mutation_rate,
-            remove_codon_mutation_rate,
-        ) = cell.dna.get_mutation_rates_no_rng();
-
         Self {
             x: cell.x,
             y: cell.y,
             food: cell.food,
             dna: cell.dna.clone(),
-            attractions: cell.attractions.clone(),
-            emissions: cell.emissions.clone(),
+            phenotype: cell.phenotype.clone(),
             disabled_codons: cell.dna.get_disabled_codons(&cell.initial_forces),
-            global_mutation_rate,
-            individual_mutation_rates,
-            primary_mutation_rate,
-            secondary_mutation_rate,
-            add_codon_mutation_rate,
-            remove_codon_mutation_rate,
-            food_to_replicate: cell.food_to_replicate,
-            size: cell.size,
+            codon_expression: cell.dna.get_codon_expression(&cell.initial_forces),
+            epigenetic_marks: cell.initial_forces.clone(),
         }
     }
 }
@@ -60,36 +52,43 @@ pub struct Cell {
     #[serde(skip)]
     pub id: u64,
     dna: DNA,
-    attractions: FxHashMap<u16, f32>,
-    emissions: Vec<(u16, f32)>,
-    food_to_replicate: f32,
-    pub size: f32,
+    pub phenotype: Phenotype,
     pub x: f32,
     pub y: f32,
     next_x: f32,
     next_y: f32,
+    velocity_x: f32,
+    velocity_y: f32,
+    force_x: f32,
+    force_y: f32,
     pub food: f32,
     last_forces: FxHashMap<u16, f32>,
+    force_average: FxHashMap<u16, f32>,
     iterations: usize,
     pub initial_forces: FxHashMap<u16, f32>,
     _initial_food_usage: f32,
+    #[serde(skip)]
+    pub chemical_deposits: DepositRecord,
 }
 
 impl Cell {
-    fn _new(
-        id: u64,
-        initial_forces: FxHashMap<u16, f32>,
-        dna: DNA,
-        x: f32,
-        y: f32,
-    ) -> (Self, Vec<usize>) {
+    fn _new(id: u64, initial_forces: FxHashMap<u16, f32>, dna: DNA, x: f32, y: f32) -> Self {
         let activated_codons = dna.get_activated_codons(&initial_forces);
-        let (attractions, emissions, food_to_replicate, size) = dna.process_dna(&activated_codons);
+        let phenotype = dna.process_dna(&activated_codons);
 
         let mut _initial_food_usage = 0.0;
-        _initial_food_usage += size * FOOD_USED_PER_SIZE_UNIT;
+        _initial_food_usage += phenotype.size * FOOD_USED_PER_SIZE_UNIT;
+        _initial_food_usage += phenotype.sensing_range_sq.sqrt() * FOOD_USED_PER_SENSING_RANGE_UNIT;
+
+        _initial_food_usage += phenotype.predation * FOOD_USED_PER_PREDATION_UNIT;
+        _initial_food_usage += phenotype.toxin_resistance * FOOD_USED_PER_TOXIN_RESISTANCE_UNIT;
+
+        for nutrient in 0..NUTRIENTS.len() {
+            _initial_food_usage +=
+                phenotype.get_digestion_efficiency(nutrient) * FOOD_USED_PER_DIGESTION_UNIT;
+        }
 
-        for (id, magnitude) in emissions.iter() {
+        for (id, magnitude) in phenotype.emissions.iter() {
             if *id == TOXIN_FORCE {
                 _initial_food_usage += *magnitude * FOOD_USED_PER_TOXIN_UNIT_EMITTED;
             } else {
@@ -97,41 +96,43 @@ impl Cell {
             }
         }
 
-        _initial_food_usage += dna.get_codon_count() as f32 * FOOD_USED_PER_CODON;
+        let charged_codons = match CODON_COST_MODEL {
+            CodonCostModel::All => dna.get_codon_count(),
+            CodonCostModel::Expressed => activated_codons.len(),
+            CodonCostModel::Regulatory => dna.get_regulatory_codon_count(),
+        };
+        _initial_food_usage += charged_codons as f32 * FOOD_USED_PER_CODON;
 
         _initial_food_usage += FOOD_USED_PER_FRAME;
 
-        (
-            Cell {
-                id,
-                dna,
-                attractions,
-                emissions,
-                food_to_replicate,
-                size,
-                x,
-                y,
-                next_x: x,
-                next_y: y,
-                food: CELL_STARTING_FOOD,
-                last_forces: FxHashMap::default(),
-                iterations: 0,
-                initial_forces,
-                _initial_food_usage,
-            },
-            activated_codons,
-        )
+        Cell {
+            id,
+            dna,
+            phenotype,
+            x,
+            y,
+            next_x: x,
+            next_y: y,
+            velocity_x: 0.0,
+            velocity_y: 0.0,
+            force_x: 0.0,
+            force_y: 0.0,
+            food: CELL_STARTING_FOOD,
+            last_forces: FxHashMap::default(),
+            force_average: initial_forces.clone(),
+            iterations: 0,
+            initial_forces,
+            _initial_food_usage,
+            chemical_deposits: DepositRecord::default(),
+        }
     }
 
-    pub fn new(id: u64, initial_forces: FxHashMap<u16, f32>) -> Self {
-        let dna = DNA::new();
-
+    pub fn new(id: u64, initial_forces: FxHashMap<u16, f32>, dna: DNA) -> Self {
         let mut rng = thread_rng();
         let x = rng.gen_range(0.0..GAME_SIZE as f32);
         let y = rng.gen_range(0.0..GAME_SIZE as f32);
 
-        let (cell, _) = Self::_new(id, initial_forces, dna, x, y);
-        cell
+        Self::_new(id, initial_forces, dna, x, y)
     }
 
     pub fn add_food(&mut self, food: f32) {
@@ -142,51 +143,158 @@ impl Cell {
         self.food -= food;
     }
 
-    pub fn add_forces(&mut self, forces: &[(u16, f32)], force_x: f32, force_y: f32) {
+    fn get_toxin_damage(&self, magnitude: f32, distance_sq: f32) -> f32 {
+        let falloff = (1.0 - distance_sq.sqrt() / FORCE_MAX_RANGE_SQ.sqrt()).max(0.0);
+        magnitude
+            * FOOD_STOLEN_PER_TOXIN_UNIT
+            * falloff.powf(TOXIN_FALLOFF_EXPONENT)
+            * (1.0 - self.phenotype.toxin_resistance)
+    }
+
+    pub fn add_forces(&mut self, forces: &[(u16, f32)], force_x: f32, force_y: f32) -> f32 {
+        let mut food_stolen = 0.0;
         for (force, magnitude) in forces {
+            let x = displacement(self.x, force_x);
+            let y = displacement(self.y, force_y);
+
+            let distance_sq = x * x + y * y;
+
             if *force == TOXIN_FORCE {
-                self.remove_food(*magnitude * FOOD_STOLEN_PER_TOXIN_UNIT);
+                let damage = self.get_toxin_damage(*magnitude, distance_sq);
+                food_stolen += damage.min(self.food.max(0.0));
+                self.remove_food(damage);
             }
 
-            *self.last_forces.entry(*force).or_insert(0.0) += *magnitude;
+            // Toxin damage falls off on its own, but forces are only felt within sensing range.
+            if distance_sq >= self.phenotype.sensing_range_sq {
+                continue;
+            }
 
-            let x = self.x - force_x;
-            let y = self.y - force_y;
+            *self.last_forces.entry(*force).or_insert(0.0) += *magnitude;
 
-            let distance_sq = x * x + y * y;
-            if distance_sq < 0.1 {
+            if distance_sq <= 0.0 {
                 continue;
             }
-            let scaled_force =
-                *magnitude * *self.attractions.get(&force).unwrap_or(&0.0) / distance_sq;
+            let scaled_force = *magnitude
+                * *self.phenotype.attractions.get(force).unwrap_or(&0.0)
+                * get_force_falloff(distance_sq)
+                * FORCE_STRENGTH
+                / distance_sq.sqrt();
 
-            self.next_x += -x * scaled_force;
-            self.next_y += -y * scaled_force;
+            self.apply_force(-x * scaled_force, -y * scaled_force);
         }
+
+        food_stolen
     }
 
-    fn update_pos(&mut self) {
-        if self.next_x < 0.0 || self.next_x >= GAME_SIZE as f32 {
-            self.next_x = self.x;
+    pub fn add_field_force(
+        &mut self,
+        force: u16,
+        concentration: f32,
+        gradient_x: f32,
+        gradient_y: f32,
+    ) {
+        if force == TOXIN_FORCE {
+            self.remove_food(
+                concentration
+                    * FOOD_STOLEN_PER_TOXIN_UNIT
+                    * (1.0 - self.phenotype.toxin_resistance),
+            );
         }
 
-        if self.next_y < 0.0 || self.next_y >= GAME_SIZE as f32 {
-            self.next_y = self.y;
+        *self.last_forces.entry(force).or_insert(0.0) += concentration;
+
+        let scaled_force = *self.phenotype.attractions.get(&force).unwrap_or(&0.0)
+            * CHEMICAL_GRADIENT_STRENGTH
+            * FORCE_STRENGTH;
+        self.apply_force(gradient_x * scaled_force, gradient_y * scaled_force);
+    }
+
+    fn apply_force(&mut self, x: f32, y: f32) {
+        match MOVEMENT_MODEL {
+            MovementModel::Direct => {
+                self.next_x += x;
+                self.next_y += y;
+            }
+            MovementModel::Inertial => {
+                self.force_x += x;
+                self.force_y += y;
+            }
         }
+    }
+
+    fn get_mass(&self) -> f32 {
+        (self.phenotype.size * MASS_PER_SIZE_UNIT).max(MIN_MASS)
+    }
+
+    fn update_velocity(&mut self) {
+        let mass = self.get_mass();
+        let drag = (1.0 - DRAG * TIME_STEP).max(0.0);
+
+        self.velocity_x = (self.velocity_x + self.force_x / mass * TIME_STEP) * drag;
+        self.velocity_y = (self.velocity_y + self.force_y / mass * TIME_STEP) * drag;
+
+        self.next_x = self.x + self.velocity_x * TIME_STEP;
+        self.next_y = self.y + self.velocity_y * TIME_STEP;
+    }
+
+    fn update_pos(&mut self) {
+        if let MovementModel::Inertial = MOVEMENT_MODEL {
+            self.update_velocity();
+        }
+
+        self.next_x = apply_boundary(self.x, self.next_x, &mut self.velocity_x);
+        self.next_y = apply_boundary(self.y, self.next_y, &mut self.velocity_y);
 
         self.x = self.next_x;
         self.y = self.next_y;
     }
 
+    pub fn displace(&mut self, x: f32, y: f32) -> (f32, f32) {
+        let prev_x = self.x;
+        let prev_y = self.y;
+
+        self.x = apply_boundary(self.x, self.x + x, &mut self.velocity_x);
+        self.y = apply_boundary(self.y, self.y + y, &mut self.velocity_y);
+        self.next_x = self.x;
+        self.next_y = self.y;
+
+        (prev_x, prev_y)
+    }
+
+    pub fn can_prey_on(&self, other: &Cell) -> bool {
+        self.phenotype.predation > 0.0
+            && self.phenotype.size >= other.phenotype.size * PREDATION_SIZE_RATIO
+    }
+
+    pub fn get_radius(&self) -> f32 {
+        self.phenotype.size.max(0.0).sqrt()
+    }
+
     fn calculate_general_food_usage(&self, prev_x: f32, prev_y: f32) -> f32 {
         let mut food_usage = self._initial_food_usage;
+        if let SenescenceModel::MetabolicCost = SENESCENCE_MODEL {
+            food_usage *= self.get_senescence();
+        }
 
-        food_usage += (self.x - prev_x).abs() * FOOD_USED_PER_UNIT_MOVED;
-        food_usage += (self.y - prev_y).abs() * FOOD_USED_PER_UNIT_MOVED;
+        match MOVEMENT_MODEL {
+            MovementModel::Direct => {
+                food_usage += displacement(self.x, prev_x).abs() * FOOD_USED_PER_UNIT_MOVED;
+                food_usage += displacement(self.y, prev_y).abs() * FOOD_USED_PER_UNIT_MOVED;
+            }
+            MovementModel::Inertial => {
+                let impulse = (self.force_x.abs() + self.force_y.abs()) * TIME_STEP;
+                food_usage += impulse * FOOD_USED_PER_UNIT_IMPULSE;
+            }
+        }
 
         food_usage
     }
 
+    fn get_senescence(&self) -> f32 {
+        1.0 + self.iterations as f32 * SENESCENCE_RATE
+    }
+
     fn update_food(&mut self, prev_x: f32, prev_y: f32) {
         let food_usage = self.calculate_general_food_usage(prev_x, prev_y);
         self.remove_food(food_usage);
@@ -198,43 +306,100 @@ impl Cell {
 
         self.update_pos();
         self.update_food(prev_x, prev_y);
+        self.force_x = 0.0;
+        self.force_y = 0.0;
 
         self.iterations += 1;
 
         (prev_x, prev_y)
     }
 
-    pub fn replicate(&mut self, id: u64) -> Cell {
-        let x_change = if self.x > 1.0 - 1.0 { -1.0 } else { 1.0 };
-        let y_change = if self.y > 1.0 - 1.0 { -1.0 } else { 1.0 };
-        let (mut new_cell, activated_codons) = Self::_new(
-            id,
-            self.last_forces.clone(),
-            self.dna.clone(),
-            self.x + x_change,
-            self.y + y_change,
-        );
-        new_cell.dna.mutate(&activated_codons);
+    fn get_dispersal_distance(&self) -> f32 {
+        match OFFSPRING_PLACEMENT {
+            OffspringPlacement::RandomDirection => OFFSPRING_DISTANCE,
+            OffspringPlacement::GeneControlled => self.phenotype.dispersal_distance,
+            OffspringPlacement::FreeSpace => 2.0 * self.get_radius() + OFFSPRING_DISTANCE,
+        }
+    }
+
+    pub fn get_offspring_position(&self) -> (f32, f32) {
+        let mut rng = thread_rng();
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let distance = self.get_dispersal_distance();
+        (
+            confine(self.x + angle.cos() * distance),
+            confine(self.y + angle.sin() * distance),
+        )
+    }
 
-        self.food -= self.food_to_replicate * (1.0 - FOOD_RETENTION_FROM_REPLICATION);
+    pub fn get_epigenetic_marks(&self) -> FxHashMap<u16, f32> {
+        match EPIGENETIC_INHERITANCE {
+            EpigeneticInheritance::DecayingAverage => self.force_average.clone(),
+            _ => self.last_forces.clone(),
+        }
+    }
+
+    pub fn replicate(
+        &mut self,
+        id: u64,
+        x: f32,
+        y: f32,
+        initial_forces: FxHashMap<u16, f32>,
+    ) -> Cell {
+        let mutation_rate_scale = match SENESCENCE_MODEL {
+            SenescenceModel::MutationRate => self.get_senescence(),
+            _ => 1.0,
+        };
+        // The DNA is mutated with the rates of the unmutated genome, and the
+        // phenotype of the offspring is then resolved from the mutated DNA.
+        let mut dna = self.dna.clone();
+        let mutation_rates = dna
+            .process_dna(&dna.get_activated_codons(&initial_forces))
+            .mutation_rates;
+        dna.mutate(&mutation_rates, mutation_rate_scale);
+        let new_cell = Self::_new(id, initial_forces, dna, x, y);
+
+        self.food -= self.phenotype.food_to_replicate * (1.0 - FOOD_RETENTION_FROM_REPLICATION);
+        if let OffspringPlacement::GeneControlled = OFFSPRING_PLACEMENT {
+            self.food -= self.phenotype.dispersal_distance * FOOD_USED_PER_UNIT_DISPERSED;
+        }
 
         new_cell
     }
 
     pub fn can_replicate(&self) -> bool {
-        self.food >= self.food_to_replicate
+        self.food >= self.phenotype.food_to_replicate
     }
 
     pub fn reset(&mut self) {
+        if let EpigeneticInheritance::DecayingAverage = EPIGENETIC_INHERITANCE {
+            for magnitude in self.force_average.values_mut() {
+                *magnitude *= EPIGENETIC_DECAY;
+            }
+            for (force, magnitude) in self.last_forces.iter() {
+                *self.force_average.entry(*force).or_insert(0.0) +=
+                    *magnitude * (1.0 - EPIGENETIC_DECAY);
+            }
+        }
+
         self.last_forces.clear();
     }
 
     pub fn is_dead(&self) -> bool {
-        self.food <= 0.0 || self.iterations >= MAX_LIFESPAN
+        self.food <= 0.0 || self.iterations as f32 >= self.phenotype.lifespan
+    }
+
+    pub fn is_inviable(&self) -> bool {
+        self.phenotype.lifespan < 1.0
+            || self.phenotype.size <= 0.0
+            || self._initial_food_usage >= CELL_STARTING_FOOD
+            || (self.phenotype.predation <= 0.0
+                && (0..NUTRIENTS.len())
+                    .all(|nutrient| self.phenotype.get_digestion_efficiency(nutrient) <= 0.0))
     }
 
     pub fn get_emissions(&self) -> Vec<(u16, f32)> {
-        self.emissions.clone()
+        self.phenotype.emissions.clone()
     }
 
     #[allow(dead_code)]
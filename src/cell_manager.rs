@@ -1,18 +1,29 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::ThreadRng;
 use rand::{thread_rng, Rng};
 
 use rustc_hash::{FxHashMap, FxHashSet};
 
+use crate::boundary::{displacement, distance_sq};
 use crate::cell::Cell;
+use crate::chemical_field::ChemicalField;
 use crate::config::*;
+use crate::dna::DNA;
+use crate::food_spawner::Spawner;
 use crate::id::IdManager;
 
+pub type Food = (f32, f32, f32, usize);
+
 pub struct CellManager {
     cells: FxHashMap<u64, Cell>,
-    food: FxHashMap<u64, (f32, f32, f32)>,
+    food: FxHashMap<u64, Food>,
+    food_counts: Vec<usize>,
+    food_spawners: Vec<Spawner>,
     cell_grid: Vec<FxHashSet<u64>>,
     food_grid: Vec<FxHashSet<u64>>,
     cell_id_manager: IdManager,
     food_id_manager: IdManager,
+    chemical_field: ChemicalField,
     _cells_per_axis: usize,
     _relation_matrix: Vec<Vec<usize>>,
 }
@@ -28,14 +39,21 @@ impl CellManager {
                 let mut neighbors = vec![];
                 for dy in -1..=1 {
                     for dx in -1..=1 {
-                        let nx = x as i32 + dx;
-                        let ny = y as i32 + dy;
+                        let mut nx = x as i32 + dx;
+                        let mut ny = y as i32 + dy;
+                        if let BoundaryMode::Wrap = BOUNDARY_MODE {
+                            nx = nx.rem_euclid(_cells_per_axis as i32);
+                            ny = ny.rem_euclid(_cells_per_axis as i32);
+                        }
                         if nx >= 0
                             && nx < _cells_per_axis as i32
                             && ny >= 0
                             && ny < _cells_per_axis as i32
                         {
-                            neighbors.push((ny as usize) * _cells_per_axis + nx as usize);
+                            let neighbor = (ny as usize) * _cells_per_axis + nx as usize;
+                            if !neighbors.contains(&neighbor) {
+                                neighbors.push(neighbor);
+                            }
                         }
                     }
                 }
@@ -47,6 +65,11 @@ impl CellManager {
         Self {
             cells: FxHashMap::default(),
             food: FxHashMap::default(),
+            food_counts: vec![0; NUTRIENTS.len()],
+            food_spawners: NUTRIENTS
+                .iter()
+                .map(|nutrient| Spawner::new(&nutrient.spawner))
+                .collect(),
             cell_grid: vec![
                 FxHashSet::default();
                 (GAME_SIZE * GAME_SIZE) / (GRID_CELL_SIZE * GRID_CELL_SIZE)
@@ -57,37 +80,39 @@ impl CellManager {
             ],
             cell_id_manager: IdManager::new(),
             food_id_manager: IdManager::new(),
+            chemical_field: ChemicalField::new(),
             _cells_per_axis,
             _relation_matrix,
         }
     }
 
     pub fn init(&mut self) {
+        self.init_with_genomes(DNA::seed_genomes(STARTING_CELLS));
+    }
+
+    pub fn init_with_genomes(&mut self, genomes: Vec<DNA>) {
         let mut rng = thread_rng();
 
-        for _ in 0..STARTING_CELLS {
+        for dna in genomes {
             let id = self.cell_id_manager.get_id();
-            let cell = Cell::new(id, FxHashMap::default());
+            let cell = Cell::new(id, FxHashMap::default(), dna);
             self.add_cell(cell);
         }
 
         for _ in 0..STARTING_FOOD {
-            let x = rng.gen_range(0.0..GAME_SIZE as f32);
-            let y = rng.gen_range(0.0..GAME_SIZE as f32);
-            let food = DEFAULT_FOOD_VALUE;
-            self.add_food(x, y, food);
+            self.spawn_food(&mut rng);
         }
     }
 
-    pub fn init_with_starting(&mut self, cells: Vec<Cell>, food: Vec<(f32, f32, f32)>) {
+    pub fn init_with_starting(&mut self, cells: Vec<Cell>, food: Vec<Food>) {
         for mut cell in cells {
             let id = self.cell_id_manager.get_id();
             cell.id = id;
             self.add_cell(cell);
         }
 
-        for (x, y, food) in food {
-            self.add_food(x, y, food);
+        for (x, y, food, nutrient) in food {
+            self.add_food(x, y, food, nutrient);
         }
     }
 
@@ -104,23 +129,48 @@ impl CellManager {
         self.cell_grid[index].insert(id);
     }
 
-    fn add_food(&mut self, x: f32, y: f32, food: f32) {
+    fn add_food(&mut self, x: f32, y: f32, food: f32, nutrient: usize) {
         let id = self.food_id_manager.get_id();
-        self.food.insert(id, (x, y, food));
+        self.food.insert(id, (x, y, food, nutrient));
+        self.food_counts[nutrient] += 1;
         let index = self.get_cell_grid_index(x, y);
         self.food_grid[index].insert(id);
     }
 
+    fn has_room_for_food(&self, nutrient: usize) -> bool {
+        self.food.len() < MAX_FOOD && self.food_counts[nutrient] < NUTRIENTS[nutrient].max_food
+    }
+
+    fn spawn_food(&mut self, rng: &mut ThreadRng) {
+        let weights = WeightedIndex::new(NUTRIENTS.iter().map(|nutrient| nutrient.spawn_weight));
+        let nutrient = weights.unwrap().sample(rng);
+        if !self.has_room_for_food(nutrient) {
+            return;
+        }
+
+        let (x, y) = self.food_spawners[nutrient].get_position(rng);
+        self.add_food(x, y, NUTRIENTS[nutrient].value, nutrient);
+    }
+
     fn remove_cell(&mut self, id: u64) {
-        if let Some(cell) = self.cells.remove(&id) {
-            let index = self.get_cell_grid_index(cell.x, cell.y);
-            self.cell_grid[index].remove(&id);
+        if self.detach_cell(id) {
             self.cell_id_manager.restore_id(id);
         }
     }
 
+    /// Removes a cell without returning its id to the id manager.
+    fn detach_cell(&mut self, id: u64) -> bool {
+        let Some(cell) = self.cells.remove(&id) else {
+            return false;
+        };
+        let index = self.get_cell_grid_index(cell.x, cell.y);
+        self.cell_grid[index].remove(&id);
+        true
+    }
+
     fn remove_food(&mut self, id: u64) {
         if let Some(food) = self.food.remove(&id) {
+            self.food_counts[food.3] -= 1;
             let index = self.get_cell_grid_index(food.0, food.1);
             self.food_grid[index].remove(&id);
             self.food_id_manager.restore_id(id);
@@ -137,29 +187,102 @@ impl CellManager {
         }
     }
 
-    fn emit_forces(&mut self, cell_keys: &Vec<u64>) {
+    fn find_overlaps(&self, cell_keys: &[u64]) -> FxHashMap<u64, (f32, f32)> {
+        let mut rng = thread_rng();
+        let mut pushes: FxHashMap<u64, (f32, f32)> = FxHashMap::default();
+        for id in cell_keys.iter() {
+            let cell = &self.cells[id];
+            let (x, y, radius, size) = (cell.x, cell.y, cell.get_radius(), cell.phenotype.size);
+            let index = self.get_cell_grid_index(x, y);
+            for neighbor in self._relation_matrix[index].iter() {
+                for other_id in self.cell_grid[*neighbor].iter() {
+                    if *other_id <= *id {
+                        continue;
+                    }
+
+                    let other = &self.cells[other_id];
+                    let min_distance = radius + other.get_radius();
+                    let dx = displacement(other.x, x);
+                    let dy = displacement(other.y, y);
+                    let distance = (dx * dx + dy * dy).sqrt();
+                    if distance >= min_distance {
+                        continue;
+                    }
+
+                    let (nx, ny) = if distance > 0.0 {
+                        (dx / distance, dy / distance)
+                    } else {
+                        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                        (angle.cos(), angle.sin())
+                    };
+
+                    let total_size = size.max(0.0) + other.phenotype.size.max(0.0);
+                    let share = if total_size > 0.0 {
+                        other.phenotype.size.max(0.0) / total_size
+                    } else {
+                        0.5
+                    };
+                    let overlap = min_distance - distance;
+
+                    let push = pushes.entry(*id).or_insert((0.0, 0.0));
+                    push.0 -= nx * overlap * share;
+                    push.1 -= ny * overlap * share;
+                    let push = pushes.entry(*other_id).or_insert((0.0, 0.0));
+                    push.0 += nx * overlap * (1.0 - share);
+                    push.1 += ny * overlap * (1.0 - share);
+                }
+            }
+        }
+
+        pushes
+    }
+
+    fn resolve_collisions(&mut self) {
+        let cell_keys: Vec<u64> = self.cells.keys().copied().collect();
+        for _ in 0..COLLISION_ITERATIONS {
+            let pushes = self.find_overlaps(&cell_keys);
+            if pushes.is_empty() {
+                break;
+            }
+
+            for (id, (x, y)) in pushes {
+                let cell = self.cells.get_mut(&id).unwrap();
+                let (prev_x, prev_y) = cell.displace(x, y);
+                let (x, y) = (cell.x, cell.y);
+                self.move_cell(id, prev_x, prev_y, x, y);
+            }
+        }
+    }
+
+    fn emit_forces(&mut self, cell_keys: &[u64]) {
         for (_, food) in self.food.iter() {
-            let (x, y, food) = *food;
+            let (x, y, food, nutrient) = *food;
+            let force = NUTRIENTS[nutrient].force;
             let index = self.get_cell_grid_index(x, y);
             let neighbors = &self._relation_matrix[index];
             for neighbor in neighbors {
                 let cells = &self.cell_grid[*neighbor];
                 for cell_id in cells.iter() {
                     if let Some(cell) = self.cells.get_mut(cell_id) {
-                        if (cell.x - x).powi(2) + (cell.y - y).powi(2) < FORCE_MAX_RANGE_SQ {
-                            cell.add_forces(&[(FOOD_FORCE, food)], x, y);
-                        }
+                        cell.add_forces(&[(force, food)], x, y);
                     }
                 }
             }
         }
 
-        for index in 0..self.cells.len() {
-            let id = cell_keys[index];
-            let cell = self.cells.get(&id).unwrap();
+        match EMISSION_MODEL {
+            EmissionModel::Pairwise => self.emit_pairwise(cell_keys),
+            EmissionModel::ChemicalField => self.emit_to_chemical_field(cell_keys),
+        }
+    }
+
+    fn emit_pairwise(&mut self, cell_keys: &[u64]) {
+        for id in cell_keys.iter() {
+            let cell = self.cells.get(id).unwrap();
             let (id, x, y, emissions) = (cell.id, cell.x, cell.y, cell.get_emissions());
             let index = self.get_cell_grid_index(x, y);
             let neighbors = &self._relation_matrix[index];
+            let mut food_stolen = 0.0;
             for neighbor in neighbors {
                 let cells = &self.cell_grid[*neighbor];
                 for cell_id in cells.iter() {
@@ -167,28 +290,107 @@ impl CellManager {
                         continue;
                     }
                     if let Some(cell) = self.cells.get_mut(cell_id) {
-                        if (cell.x - x).powi(2) + (cell.y - y).powi(2) < FORCE_MAX_RANGE_SQ {
-                            cell.add_forces(&emissions, x, y);
-                        }
+                        food_stolen += cell.add_forces(&emissions, x, y);
+                    }
+                }
+            }
+
+            let cell = self.cells.get_mut(&id).unwrap();
+            cell.add_food(food_stolen * TOXIN_FOOD_TRANSFER);
+        }
+    }
+
+    fn emit_to_chemical_field(&mut self, cell_keys: &[u64]) {
+        for id in cell_keys.iter() {
+            let cell = self.cells.get_mut(id).unwrap();
+            for (force, magnitude) in cell.phenotype.emissions.iter() {
+                self.chemical_field.deposit(
+                    &mut cell.chemical_deposits,
+                    *force,
+                    cell.x,
+                    cell.y,
+                    *magnitude * CHEMICAL_DEPOSIT_RATE,
+                );
+            }
+        }
+
+        self.chemical_field.update();
+
+        let forces: Vec<u16> = self.chemical_field.get_forces().collect();
+        for id in cell_keys.iter() {
+            let cell = self.cells.get_mut(id).unwrap();
+            self.chemical_field
+                .update_record(&mut cell.chemical_deposits);
+            for force in forces.iter() {
+                let concentration = self.chemical_field.get_concentration(
+                    *force,
+                    cell.x,
+                    cell.y,
+                    Some(&cell.chemical_deposits),
+                );
+                let (gradient_x, gradient_y) = self.chemical_field.get_gradient(
+                    *force,
+                    cell.x,
+                    cell.y,
+                    Some(&cell.chemical_deposits),
+                );
+                cell.add_field_force(*force, concentration.max(0.0), gradient_x, gradient_y);
+            }
+        }
+    }
+
+    fn sense_forces(&self, x: f32, y: f32, range_sq: f32) -> FxHashMap<u16, f32> {
+        let mut forces = FxHashMap::default();
+        let index = self.get_cell_grid_index(x, y);
+        for neighbor in self._relation_matrix[index].iter() {
+            for food_id in self.food_grid[*neighbor].iter() {
+                let (food_x, food_y, food, nutrient) = self.food[food_id];
+                if distance_sq(food_x, food_y, x, y) < range_sq {
+                    *forces.entry(NUTRIENTS[nutrient].force).or_insert(0.0) += food;
+                }
+            }
+
+            if let EmissionModel::ChemicalField = EMISSION_MODEL {
+                continue;
+            }
+
+            for cell_id in self.cell_grid[*neighbor].iter() {
+                let cell = &self.cells[cell_id];
+                if distance_sq(cell.x, cell.y, x, y) < range_sq {
+                    for (force, magnitude) in cell.phenotype.emissions.iter() {
+                        *forces.entry(*force).or_insert(0.0) += *magnitude;
                     }
                 }
             }
         }
+
+        if let EmissionModel::ChemicalField = EMISSION_MODEL {
+            for force in self.chemical_field.get_forces() {
+                *forces.entry(force).or_insert(0.0) +=
+                    self.chemical_field.get_concentration(force, x, y, None);
+            }
+        }
+
+        forces
     }
 
     fn attempt_to_eat(&mut self, cell_id: u64) {
         let cell = self.cells.get(&cell_id).unwrap();
-        let (x, y, size) = (cell.x, cell.y, cell.size);
+        let (x, y, size) = (cell.x, cell.y, cell.phenotype.size);
         let index = self.get_cell_grid_index(x, y);
         let neighbors = self._relation_matrix[index].clone();
         for neighbor in neighbors {
             let foods = self.food_grid[neighbor].clone();
             for food_id in foods.iter() {
                 if let Some(food) = self.food.get(food_id) {
-                    let (food_x, food_y, food) = *food;
-                    if (x - food_x).powi(2) + (y - food_y).powi(2) <= size {
+                    let (food_x, food_y, food, nutrient) = *food;
+                    if distance_sq(x, y, food_x, food_y) <= size {
                         let cell = self.cells.get_mut(&cell_id).unwrap();
-                        cell.add_food(food);
+                        let efficiency = cell.phenotype.get_digestion_efficiency(nutrient);
+                        if efficiency <= 0.0 {
+                            continue;
+                        }
+                        cell.add_food(food * efficiency);
                         let food_id = *food_id;
                         self.remove_food(food_id);
                     }
@@ -197,23 +399,71 @@ impl CellManager {
         }
     }
 
+    fn is_space_free(&self, x: f32, y: f32, radius: f32) -> bool {
+        let index = self.get_cell_grid_index(x, y);
+        self._relation_matrix[index].iter().all(|neighbor| {
+            self.cell_grid[*neighbor].iter().all(|cell_id| {
+                let cell = &self.cells[cell_id];
+                distance_sq(cell.x, cell.y, x, y) >= (radius + cell.get_radius()).powi(2)
+            })
+        })
+    }
+
+    fn find_offspring_position(&self, cell: &Cell) -> Option<(f32, f32)> {
+        match OFFSPRING_PLACEMENT {
+            OffspringPlacement::FreeSpace => (0..OFFSPRING_PLACEMENT_ATTEMPTS)
+                .map(|_| cell.get_offspring_position())
+                .find(|&(x, y)| self.is_space_free(x, y, cell.get_radius())),
+            _ => Some(cell.get_offspring_position()),
+        }
+    }
+
+    fn attempt_to_prey(&mut self, cell_id: u64) -> Option<u64> {
+        let cell = &self.cells[&cell_id];
+        if cell.phenotype.predation <= 0.0 {
+            return None;
+        }
+
+        let (x, y, size) = (cell.x, cell.y, cell.phenotype.size);
+        let index = self.get_cell_grid_index(x, y);
+        let prey_id = self._relation_matrix[index].iter().find_map(|neighbor| {
+            self.cell_grid[*neighbor].iter().copied().find(|prey_id| {
+                let prey = &self.cells[prey_id];
+                *prey_id != cell_id
+                    && cell.can_prey_on(prey)
+                    && distance_sq(x, y, prey.x, prey.y) <= size
+            })
+        })?;
+
+        let food = self.cells[&prey_id].food.max(0.0);
+        let cell = self.cells.get_mut(&cell_id).unwrap();
+        cell.add_food(food * cell.phenotype.predation);
+        self.detach_cell(prey_id);
+        Some(prey_id)
+    }
+
     pub fn update(&mut self) {
         let mut rng = thread_rng();
         let cell_keys: Vec<u64> = self.cells.keys().copied().collect();
         let mut cells_length = cell_keys.len();
+        // Ids of eaten cells are only released after the loop, so a newborn
+        // can't take over the id of a cell that is still waiting to be updated.
+        let mut eaten_ids = Vec::new();
 
         self.emit_forces(&cell_keys);
 
         for id in cell_keys.iter() {
-            let cell = self.cells.get_mut(&id).unwrap();
+            let Some(cell) = self.cells.get_mut(id) else {
+                continue;
+            };
             let (prev_x, prev_y) = cell.update();
             let (x, y) = (cell.x, cell.y);
             self.move_cell(*id, prev_x, prev_y, x, y);
 
-            let cell = self.cells.get(&id).unwrap();
+            let cell = self.cells.get(id).unwrap();
             if cell.is_dead() {
-                if self.food.len() < MAX_FOOD {
-                    self.add_food(cell.x, cell.y, DEFAULT_CELL_FOOD_VALUE);
+                if self.has_room_for_food(CORPSE_NUTRIENT) {
+                    self.add_food(cell.x, cell.y, DEFAULT_CELL_FOOD_VALUE, CORPSE_NUTRIENT);
                 }
                 self.remove_cell(*id);
                 cells_length -= 1;
@@ -221,27 +471,49 @@ impl CellManager {
             }
 
             self.attempt_to_eat(*id);
+            if let Some(prey_id) = self.attempt_to_prey(*id) {
+                eaten_ids.push(prey_id);
+                cells_length -= 1;
+            }
 
-            let cell = self.cells.get_mut(&id).unwrap();
-            if cell.can_replicate() && cells_length < MAX_CELLS {
-                let id = self.cell_id_manager.get_id();
-                let new_cell = cell.replicate(id);
+            let cell = self.cells.get(id).unwrap();
+            let offspring_position = if cell.can_replicate() && cells_length < MAX_CELLS {
+                self.find_offspring_position(cell)
+            } else {
+                None
+            };
+            if let Some((x, y)) = offspring_position {
+                let initial_forces = match EPIGENETIC_INHERITANCE {
+                    // The offspring's own range depends on these forces, so the parent's is used.
+                    EpigeneticInheritance::SenseAtBirth => {
+                        self.sense_forces(x, y, cell.phenotype.sensing_range_sq)
+                    }
+                    _ => cell.get_epigenetic_marks(),
+                };
+
+                let new_id = self.cell_id_manager.get_id();
+                let cell = self.cells.get_mut(id).unwrap();
+                let new_cell = cell.replicate(new_id, x, y, initial_forces);
                 cell.reset();
                 self.add_cell(new_cell);
                 cells_length += 1;
                 continue;
             }
 
+            let cell = self.cells.get_mut(id).unwrap();
             cell.reset();
         }
 
-        if self.food.len() < MAX_FOOD {
-            for _ in 0..FOOD_ADDED_PER_FRAME {
-                let x = rng.gen_range(0.0..GAME_SIZE as f32);
-                let y = rng.gen_range(0.0..GAME_SIZE as f32);
-                let food = DEFAULT_FOOD_VALUE;
-                self.add_food(x, y, food);
-            }
+        for id in eaten_ids {
+            self.cell_id_manager.restore_id(id);
+        }
+
+        if CELL_COLLISIONS {
+            self.resolve_collisions();
+        }
+
+        for _ in 0..FOOD_ADDED_PER_FRAME {
+            self.spawn_food(&mut rng);
         }
     }
 
@@ -253,7 +525,41 @@ impl CellManager {
         self.cells.clone()
     }
 
-    pub fn get_food_cloned(&self) -> FxHashMap<u64, (f32, f32, f32)> {
+    pub fn get_food_cloned(&self) -> FxHashMap<u64, Food> {
         self.food.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lone_toxin_emitter_takes_no_damage() {
+        let dna: DNA =
+            serde_json::from_str(&format!("[[1, {}, {}]]", TOXIN_FORCE, MAX_TOXIN_FORCE)).unwrap();
+        let mut cell_manager = CellManager::new();
+        let id = cell_manager.cell_id_manager.get_id();
+        cell_manager.add_cell(Cell::new(id, FxHashMap::default(), dna));
+
+        for frame in 0..500 {
+            // Move back and forth across several squares so the cell keeps
+            // running into the chemical it left behind.
+            let cell = cell_manager.cells.get_mut(&id).unwrap();
+            cell.x = 500.0 + (frame % 40) as f32 * 5.0;
+            cell.y = 500.0;
+            let food = cell.food;
+
+            cell_manager.emit_to_chemical_field(&[id]);
+
+            let cell = &cell_manager.cells[&id];
+            assert!(
+                cell_manager
+                    .chemical_field
+                    .get_concentration(TOXIN_FORCE, cell.x, cell.y, None)
+                    > 0.0
+            );
+            assert_eq!(cell.food, food);
+        }
+    }
+}
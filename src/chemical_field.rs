@@ -0,0 +1,189 @@
+use rustc_hash::FxHashMap;
+
+use crate::config::*;
+
+const MIN_CONCENTRATION: f32 = 1e-6;
+
+/// The chemical a single cell has deposited, diffused and decayed in the same
+/// way as the field itself, so that the cell doesn't sense its own emissions.
+#[derive(Clone, Default)]
+pub struct DepositRecord {
+    layers: FxHashMap<u16, FxHashMap<usize, f32>>,
+}
+
+impl DepositRecord {
+    fn get_amount(&self, force: u16, index: usize) -> f32 {
+        self.layers
+            .get(&force)
+            .and_then(|layer| layer.get(&index))
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+pub struct ChemicalField {
+    layers: FxHashMap<u16, Vec<f32>>,
+    cells_per_axis: usize,
+    neighbors: Vec<[usize; 4]>,
+}
+
+impl ChemicalField {
+    pub fn new() -> Self {
+        let mut field = Self {
+            layers: FxHashMap::default(),
+            cells_per_axis: GAME_SIZE.div_ceil(CHEMICAL_FIELD_CELL_SIZE),
+            neighbors: Vec::new(),
+        };
+
+        let axis = field.cells_per_axis;
+        for y in 0..axis {
+            for x in 0..axis {
+                let neighbors = [
+                    y * axis + field.get_neighbor(x, -1),
+                    y * axis + field.get_neighbor(x, 1),
+                    field.get_neighbor(y, -1) * axis + x,
+                    field.get_neighbor(y, 1) * axis + x,
+                ];
+                field.neighbors.push(neighbors);
+            }
+        }
+
+        field
+    }
+
+    fn get_coordinates(&self, x: f32, y: f32) -> (usize, usize) {
+        let x = (x / CHEMICAL_FIELD_CELL_SIZE as f32).floor() as usize;
+        let y = (y / CHEMICAL_FIELD_CELL_SIZE as f32).floor() as usize;
+        (
+            x.min(self.cells_per_axis - 1),
+            y.min(self.cells_per_axis - 1),
+        )
+    }
+
+    fn get_index(&self, x: f32, y: f32) -> usize {
+        let (x, y) = self.get_coordinates(x, y);
+        y * self.cells_per_axis + x
+    }
+
+    fn get_neighbor(&self, coordinate: usize, offset: isize) -> usize {
+        let neighbor = coordinate as isize + offset;
+        match BOUNDARY_MODE {
+            BoundaryMode::Wrap => neighbor.rem_euclid(self.cells_per_axis as isize) as usize,
+            _ if neighbor < 0 || neighbor >= self.cells_per_axis as isize => coordinate,
+            _ => neighbor as usize,
+        }
+    }
+
+    fn diffuse(neighbors: &[usize; 4], index: usize, previous: impl Fn(usize) -> f32) -> f32 {
+        let diffusion_rate = CHEMICAL_DIFFUSION_RATE.clamp(0.0, 0.25);
+        let laplacian = neighbors
+            .iter()
+            .map(|neighbor| previous(*neighbor))
+            .sum::<f32>()
+            - 4.0 * previous(index);
+        let concentration =
+            (previous(index) + diffusion_rate * laplacian) * (1.0 - CHEMICAL_DECAY_RATE);
+        if concentration.abs() < MIN_CONCENTRATION {
+            0.0
+        } else {
+            concentration
+        }
+    }
+
+    pub fn deposit(&mut self, record: &mut DepositRecord, force: u16, x: f32, y: f32, amount: f32) {
+        let index = self.get_index(x, y);
+        let size = self.cells_per_axis * self.cells_per_axis;
+        self.layers.entry(force).or_insert_with(|| vec![0.0; size])[index] += amount;
+        *record
+            .layers
+            .entry(force)
+            .or_default()
+            .entry(index)
+            .or_insert(0.0) += amount;
+    }
+
+    pub fn update(&mut self) {
+        for layer in self.layers.values_mut() {
+            let previous = layer.clone();
+            for (index, concentration) in layer.iter_mut().enumerate() {
+                *concentration =
+                    Self::diffuse(&self.neighbors[index], index, |neighbor| previous[neighbor]);
+            }
+        }
+    }
+
+    /// Applies the same update to a record as `update` applies to the field,
+    /// only visiting the squares the record has reached.
+    pub fn update_record(&self, record: &mut DepositRecord) {
+        for layer in record.layers.values_mut() {
+            let mut indices: Vec<usize> = layer
+                .keys()
+                .flat_map(|&index| std::iter::once(index).chain(self.neighbors[index]))
+                .collect();
+            indices.sort_unstable();
+            indices.dedup();
+
+            let previous = std::mem::take(layer);
+            for index in indices {
+                let concentration = Self::diffuse(&self.neighbors[index], index, |neighbor| {
+                    previous.get(&neighbor).copied().unwrap_or(0.0)
+                });
+                if concentration != 0.0 {
+                    layer.insert(index, concentration);
+                }
+            }
+        }
+        record.layers.retain(|_, layer| !layer.is_empty());
+    }
+
+    pub fn get_forces(&self) -> impl Iterator<Item = u16> + '_ {
+        self.layers.keys().copied()
+    }
+
+    /// The concentration at a square, excluding what the given record deposited.
+    fn get_foreign_concentration(
+        &self,
+        layer: &[f32],
+        force: u16,
+        index: usize,
+        excluded: Option<&DepositRecord>,
+    ) -> f32 {
+        layer[index] - excluded.map_or(0.0, |record| record.get_amount(force, index))
+    }
+
+    pub fn get_concentration(
+        &self,
+        force: u16,
+        x: f32,
+        y: f32,
+        excluded: Option<&DepositRecord>,
+    ) -> f32 {
+        let Some(layer) = self.layers.get(&force) else {
+            return 0.0;
+        };
+        self.get_foreign_concentration(layer, force, self.get_index(x, y), excluded)
+    }
+
+    pub fn get_gradient(
+        &self,
+        force: u16,
+        x: f32,
+        y: f32,
+        excluded: Option<&DepositRecord>,
+    ) -> (f32, f32) {
+        let Some(layer) = self.layers.get(&force) else {
+            return (0.0, 0.0);
+        };
+        let (x, y) = self.get_coordinates(x, y);
+        let axis = self.cells_per_axis;
+        let distance = 2.0 * CHEMICAL_FIELD_CELL_SIZE as f32;
+        let concentration = |index| self.get_foreign_concentration(layer, force, index, excluded);
+        let gradient_x = (concentration(y * axis + self.get_neighbor(x, 1))
+            - concentration(y * axis + self.get_neighbor(x, -1)))
+            / distance;
+        let gradient_y = (concentration(self.get_neighbor(y, 1) * axis + x)
+            - concentration(self.get_neighbor(y, -1) * axis + x))
+            / distance;
+        (gradient_x, gradient_y)
+    }
+}
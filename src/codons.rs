@@ -0,0 +1,700 @@
+use rand::rngs::ThreadRng;
+use rand::Rng;
+
+use rustc_hash::FxHashMap;
+
+use crate::config::*;
+
+pub type Codon = (u8, u16, f32);
+
+pub const INERT_PRIMARY_BASE: u8 = u8::MAX;
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Phenotype {
+    pub attractions: FxHashMap<u16, f32>,
+    pub emissions: Vec<(u16, f32)>,
+    pub food_to_replicate: f32,
+    pub size: f32,
+    pub sensing_range_sq: f32,
+    pub lifespan: f32,
+    pub predation: f32,
+    pub toxin_resistance: f32,
+    pub dispersal_distance: f32,
+    pub digestion_efficiencies: FxHashMap<u16, f32>,
+    pub mutation_rates: MutationRates,
+}
+
+impl Default for Phenotype {
+    fn default() -> Self {
+        Self {
+            attractions: FxHashMap::default(),
+            emissions: Vec::new(),
+            food_to_replicate: DEFAULT_FOOD_TO_REPLICATE,
+            size: DEFAULT_CELL_SIZE_SQ,
+            sensing_range_sq: DEFAULT_SENSING_RANGE_SQ,
+            lifespan: DEFAULT_LIFESPAN,
+            predation: 0.0,
+            toxin_resistance: 0.0,
+            dispersal_distance: DEFAULT_DISPERSAL_DISTANCE,
+            digestion_efficiencies: FxHashMap::default(),
+            mutation_rates: MutationRates::default(),
+        }
+    }
+}
+
+impl Phenotype {
+    pub fn get_digestion_efficiency(&self, nutrient: usize) -> f32 {
+        *self
+            .digestion_efficiencies
+            .get(&(nutrient as u16))
+            .unwrap_or(&NUTRIENTS[nutrient].default_efficiency)
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MutationRates {
+    pub global_mutation_rate: f32,
+    pub individual_mutation_rates: FxHashMap<usize, f32>,
+    pub primary_mutation_rate: f32,
+    pub secondary_mutation_rate: f32,
+    pub add_codon_mutation_rate: f32,
+    pub remove_codon_mutation_rate: f32,
+    pub duplication_mutation_rate: f32,
+    pub inversion_mutation_rate: f32,
+    pub translocation_mutation_rate: f32,
+    pub mutation_step_size: f32,
+}
+
+impl Default for MutationRates {
+    fn default() -> Self {
+        Self {
+            global_mutation_rate: DEFAULT_MUTATION_RATE,
+            individual_mutation_rates: FxHashMap::default(),
+            primary_mutation_rate: DEFAULT_PRIMARY_MUTATION_RATE,
+            secondary_mutation_rate: DEFAULT_SECONDARY_MUTATION_RATE,
+            add_codon_mutation_rate: DEFAULT_ADD_CODON_MUTATION_RATE,
+            remove_codon_mutation_rate: DEFAULT_REMOVE_CODON_MUTATION_RATE,
+            duplication_mutation_rate: DEFAULT_DUPLICATION_MUTATION_RATE,
+            inversion_mutation_rate: DEFAULT_INVERSION_MUTATION_RATE,
+            translocation_mutation_rate: DEFAULT_TRANSLOCATION_MUTATION_RATE,
+            mutation_step_size: DEFAULT_MUTATION_STEP_SIZE,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Regulation {
+    pub enable: bool,
+    pub below_threshold: bool,
+    pub range: bool,
+}
+
+/// A kind of codon, identified by its index in `CODON_TYPES` (the primary base).
+///
+/// Adding a gene means implementing this trait and appending it to `CODON_TYPES`.
+pub trait CodonType: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Generates the secondary base and value of a new codon of this type.
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32);
+
+    /// Standard deviation of a value mutation before it is scaled by the mutation step size.
+    fn mutation_scale(&self) -> f32 {
+        1.0
+    }
+
+    /// Clamps a mutated secondary base and value back into their valid ranges.
+    fn fix(&self, _secondary_base: &mut u16, _value: &mut f32) {}
+
+    fn express(&self, _secondary_base: u16, _value: f32, _phenotype: &mut Phenotype) {}
+
+    fn regulation(&self) -> Option<Regulation> {
+        None
+    }
+
+    fn is_transposon(&self) -> bool {
+        false
+    }
+
+    /// Codons of the same type with the same key override each other, so only the last active one is expressed.
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        None
+    }
+}
+
+struct Attraction;
+
+impl CodonType for Attraction {
+    fn name(&self) -> &'static str {
+        "Attraction"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (rng.gen_range(0..=10), rng.gen_range(-10.0..=10.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        1.0
+    }
+
+    fn express(&self, secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        *phenotype.attractions.entry(secondary_base).or_insert(0.0) += value;
+    }
+}
+
+fn is_nutrient_force(force: u16) -> bool {
+    NUTRIENTS.iter().any(|nutrient| nutrient.force == force)
+}
+
+struct Emission;
+
+impl CodonType for Emission {
+    fn name(&self) -> &'static str {
+        "Emission"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        let secondary_force = rng.gen_range(0..=10);
+        if is_nutrient_force(secondary_force) || secondary_force == TOXIN_FORCE {
+            (TOXIN_FORCE, rng.gen_range(0.0..=MAX_TOXIN_FORCE))
+        } else {
+            (secondary_force, rng.gen_range(-10.0..=10.0))
+        }
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.5
+    }
+
+    fn fix(&self, secondary_base: &mut u16, value: &mut f32) {
+        if is_nutrient_force(*secondary_base) {
+            *secondary_base = TOXIN_FORCE;
+        }
+
+        if *secondary_base == TOXIN_FORCE && *value > MAX_TOXIN_FORCE {
+            *value = MAX_TOXIN_FORCE;
+        }
+
+        if *value < 0.0 {
+            *value = 0.0;
+        }
+    }
+
+    fn express(&self, secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        if let Some(emission) = phenotype
+            .emissions
+            .iter_mut()
+            .find(|x| x.0 == secondary_base)
+        {
+            emission.1 += value;
+        } else {
+            phenotype.emissions.push((secondary_base, value));
+        }
+    }
+}
+
+struct Regulatory {
+    name: &'static str,
+    regulation: Regulation,
+}
+
+impl CodonType for Regulatory {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (rng.gen_range(0..=10), rng.gen_range(0.0..=100.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        5.0
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.max(0.0);
+    }
+
+    fn regulation(&self) -> Option<Regulation> {
+        Some(self.regulation)
+    }
+}
+
+struct MutationRate {
+    name: &'static str,
+    rate: fn(&mut MutationRates) -> &mut f32,
+}
+
+impl CodonType for MutationRate {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=1.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.01
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, 1.0);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        *(self.rate)(&mut phenotype.mutation_rates) = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct IndividualMutationRate;
+
+impl CodonType for IndividualMutationRate {
+    fn name(&self) -> &'static str {
+        "IndividualMutationRate"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=1.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.01
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, 1.0);
+    }
+
+    fn express(&self, secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype
+            .mutation_rates
+            .individual_mutation_rates
+            .insert(secondary_base as usize, value);
+    }
+
+    fn override_key(&self, secondary_base: u16) -> Option<u16> {
+        Some(secondary_base)
+    }
+}
+
+struct ReplicationFood;
+
+impl CodonType for ReplicationFood {
+    fn name(&self) -> &'static str {
+        "ReplicationFood"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (
+            0,
+            rng.gen_range(
+                CELL_STARTING_FOOD * MIN_FOOD_TO_REPLICATE_RATIO
+                    ..=CELL_STARTING_FOOD * MIN_FOOD_TO_REPLICATE_RATIO * 2.0,
+            ),
+        )
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        5.0
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.max(CELL_STARTING_FOOD * MIN_FOOD_TO_REPLICATE_RATIO);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.food_to_replicate = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct CellSize;
+
+impl CodonType for CellSize {
+    fn name(&self) -> &'static str {
+        "CellSize"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.1..=DEFAULT_CELL_SIZE_SQ * 2.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        1.0
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.max(0.0);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.size = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct SensingRange;
+
+impl CodonType for SensingRange {
+    fn name(&self) -> &'static str {
+        "SensingRange"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=FORCE_MAX_RANGE_SQ))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        500.0
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, FORCE_MAX_RANGE_SQ);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.sensing_range_sq = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct Lifespan;
+
+impl CodonType for Lifespan {
+    fn name(&self) -> &'static str {
+        "Lifespan"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=MAX_LIFESPAN as f32))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        250.0
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, MAX_LIFESPAN as f32);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.lifespan = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct MutationStepSize;
+
+impl CodonType for MutationStepSize {
+    fn name(&self) -> &'static str {
+        "MutationStepSize"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=DEFAULT_MUTATION_STEP_SIZE * 2.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.1
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, MAX_MUTATION_STEP_SIZE);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.mutation_rates.mutation_step_size = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct Transposon;
+
+impl CodonType for Transposon {
+    fn name(&self) -> &'static str {
+        "Transposon"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (
+            rng.gen_range(0..=MAX_TRANSPOSON_LENGTH as u16),
+            rng.gen_range(0.0..=0.1),
+        )
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.01
+    }
+
+    fn fix(&self, secondary_base: &mut u16, value: &mut f32) {
+        *secondary_base = (*secondary_base).min(MAX_TRANSPOSON_LENGTH as u16);
+        *value = value.clamp(0.0, 1.0);
+    }
+
+    fn is_transposon(&self) -> bool {
+        true
+    }
+}
+
+struct Predation;
+
+impl CodonType for Predation {
+    fn name(&self) -> &'static str {
+        "Predation"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=1.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.05
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, 1.0);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.predation = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct ToxinResistance;
+
+impl CodonType for ToxinResistance {
+    fn name(&self) -> &'static str {
+        "ToxinResistance"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=1.0))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.05
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, 1.0);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.toxin_resistance = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct DispersalDistance;
+
+impl CodonType for DispersalDistance {
+    fn name(&self) -> &'static str {
+        "DispersalDistance"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (0, rng.gen_range(0.0..=MAX_DISPERSAL_DISTANCE))
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        1.0
+    }
+
+    fn fix(&self, _secondary_base: &mut u16, value: &mut f32) {
+        *value = value.clamp(0.0, MAX_DISPERSAL_DISTANCE);
+    }
+
+    fn express(&self, _secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype.dispersal_distance = value;
+    }
+
+    fn override_key(&self, _secondary_base: u16) -> Option<u16> {
+        Some(0)
+    }
+}
+
+struct Digestion;
+
+impl CodonType for Digestion {
+    fn name(&self) -> &'static str {
+        "Digestion"
+    }
+
+    fn random(&self, rng: &mut ThreadRng) -> (u16, f32) {
+        (
+            rng.gen_range(0..NUTRIENTS.len() as u16),
+            rng.gen_range(0.0..=MAX_DIGESTION_EFFICIENCY),
+        )
+    }
+
+    fn mutation_scale(&self) -> f32 {
+        0.05
+    }
+
+    fn fix(&self, secondary_base: &mut u16, value: &mut f32) {
+        *secondary_base = (*secondary_base).min(NUTRIENTS.len() as u16 - 1);
+        *value = value.clamp(0.0, MAX_DIGESTION_EFFICIENCY);
+    }
+
+    fn express(&self, secondary_base: u16, value: f32, phenotype: &mut Phenotype) {
+        phenotype
+            .digestion_efficiencies
+            .insert(secondary_base, value);
+    }
+
+    fn override_key(&self, secondary_base: u16) -> Option<u16> {
+        Some(secondary_base)
+    }
+}
+
+pub static CODON_TYPES: &[&dyn CodonType] = &[
+    &Attraction,
+    &Emission,
+    &Regulatory {
+        name: "DisableCodon",
+        regulation: Regulation {
+            enable: false,
+            below_threshold: false,
+            range: false,
+        },
+    },
+    &MutationRate {
+        name: "GlobalMutationRate",
+        rate: |rates| &mut rates.global_mutation_rate,
+    },
+    &IndividualMutationRate,
+    &MutationRate {
+        name: "PrimaryMutationRate",
+        rate: |rates| &mut rates.primary_mutation_rate,
+    },
+    &MutationRate {
+        name: "SecondaryMutationRate",
+        rate: |rates| &mut rates.secondary_mutation_rate,
+    },
+    &MutationRate {
+        name: "AddCodonMutationRate",
+        rate: |rates| &mut rates.add_codon_mutation_rate,
+    },
+    &MutationRate {
+        name: "RemoveCodonMutationRate",
+        rate: |rates| &mut rates.remove_codon_mutation_rate,
+    },
+    &ReplicationFood,
+    &CellSize,
+    &Regulatory {
+        name: "EnableCodon",
+        regulation: Regulation {
+            enable: true,
+            below_threshold: false,
+            range: false,
+        },
+    },
+    &Regulatory {
+        name: "DisableCodonRange",
+        regulation: Regulation {
+            enable: false,
+            below_threshold: false,
+            range: true,
+        },
+    },
+    &Regulatory {
+        name: "DisableCodonBelow",
+        regulation: Regulation {
+            enable: false,
+            below_threshold: true,
+            range: false,
+        },
+    },
+    &Regulatory {
+        name: "EnableCodonBelow",
+        regulation: Regulation {
+            enable: true,
+            below_threshold: true,
+            range: false,
+        },
+    },
+    &Regulatory {
+        name: "DisableCodonRangeBelow",
+        regulation: Regulation {
+            enable: false,
+            below_threshold: true,
+            range: true,
+        },
+    },
+    &MutationRate {
+        name: "DuplicationMutationRate",
+        rate: |rates| &mut rates.duplication_mutation_rate,
+    },
+    &MutationRate {
+        name: "InversionMutationRate",
+        rate: |rates| &mut rates.inversion_mutation_rate,
+    },
+    &MutationRate {
+        name: "TranslocationMutationRate",
+        rate: |rates| &mut rates.translocation_mutation_rate,
+    },
+    &SensingRange,
+    &Lifespan,
+    &MutationStepSize,
+    &Transposon,
+    &Predation,
+    &ToxinResistance,
+    &DispersalDistance,
+    &Digestion,
+];
+
+/// Returns the codon type for a primary base, or `None` if it is unknown or disabled in this experiment.
+pub fn get_codon_type(primary_base: u8) -> Option<&'static dyn CodonType> {
+    if DISABLED_PRIMARY_BASES.contains(&primary_base) {
+        return None;
+    }
+
+    CODON_TYPES.get(primary_base as usize).copied()
+}
+
+pub fn format_codon((primary_base, secondary_base, value): Codon) -> String {
+    let name = if primary_base == INERT_PRIMARY_BASE {
+        "Inert"
+    } else {
+        CODON_TYPES
+            .get(primary_base as usize)
+            .map_or("Unknown", |codon_type| codon_type.name())
+    };
+    format!("{}({}, {:.4})", name, secondary_base, value)
+}
+
+pub fn random_primary_base(rng: &mut ThreadRng) -> u8 {
+    let mut enabled_primary_bases = (0..CODON_TYPES.len() as u8)
+        .filter(|primary_base| !DISABLED_PRIMARY_BASES.contains(primary_base));
+    let enabled_count = enabled_primary_bases.clone().count();
+    enabled_primary_bases
+        .nth(rng.gen_range(0..enabled_count))
+        .unwrap()
+}
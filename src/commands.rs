@@ -0,0 +1,175 @@
+use rustc_hash::FxHashMap;
+
+use crate::cell::Cell;
+use crate::cell_manager::CellManager;
+use crate::codons::format_codon;
+use crate::config::*;
+use crate::diff::CodonDiff;
+use crate::dna::DNA;
+
+const DEFAULT_ROBUSTNESS_MUTANTS: usize = 10000;
+const DEFAULT_KNOCKOUT_ITERATIONS: usize = 1000;
+const DEFAULT_KNOCKOUT_REPLICATES: usize = 5;
+
+fn print_fraction(label: &str, count: usize, total: usize) {
+    println!(
+        "{}: {} ({:.2}%)",
+        label,
+        count,
+        count as f32 / total.max(1) as f32 * 100.0
+    );
+}
+
+fn load_genome(path: &str) -> Option<DNA> {
+    match DNA::load(path) {
+        Ok(dna) => Some(dna),
+        Err(error) => {
+            println!("Could not load {}: {}", path, error);
+            None
+        }
+    }
+}
+
+fn parse_count(arg: Option<&String>, default: usize) -> Option<usize> {
+    arg.map_or(Some(default), |arg| arg.parse().ok())
+}
+
+pub fn diff(args: &[String]) {
+    if args.len() != 2 {
+        println!("Usage: CellSimulation diff <ancestor.json> <descendant.json>");
+        return;
+    }
+
+    let Some(ancestor) = load_genome(&args[0]) else {
+        return;
+    };
+    let Some(descendant) = load_genome(&args[1]) else {
+        return;
+    };
+
+    let (mut substitutions, mut value_changes, mut insertions, mut deletions) = (0, 0, 0, 0);
+    for codon_diff in ancestor.diff(&descendant) {
+        match codon_diff {
+            CodonDiff::Unchanged { .. } => continue,
+            CodonDiff::ValueChanged { .. } => value_changes += 1,
+            CodonDiff::Substituted { .. } => substitutions += 1,
+            CodonDiff::Inserted { .. } => insertions += 1,
+            CodonDiff::Deleted { .. } => deletions += 1,
+        }
+        println!("{}", codon_diff);
+    }
+
+    println!(
+        "{} substitutions, {} value changes, {} insertions, {} deletions",
+        substitutions, value_changes, insertions, deletions
+    );
+}
+
+pub fn robustness(args: &[String]) {
+    let mutants = parse_count(args.get(1), DEFAULT_ROBUSTNESS_MUTANTS);
+    let (Some(mutants), 1..=2) = (mutants, args.len()) else {
+        println!("Usage: CellSimulation robustness <genome.json> [mutants]");
+        return;
+    };
+
+    let Some(dna) = load_genome(&args[0]) else {
+        return;
+    };
+
+    let original = Cell::new(0, FxHashMap::default(), dna.clone());
+    let (mut unchanged, mut neutral, mut phenotype_changing, mut lethal) = (0, 0, 0, 0);
+    for _ in 0..mutants {
+        let mut mutant_dna = dna.clone();
+        mutant_dna.mutate(&original.phenotype.mutation_rates, 1.0);
+        if mutant_dna == dna {
+            unchanged += 1;
+            continue;
+        }
+
+        let mutant = Cell::new(0, FxHashMap::default(), mutant_dna);
+        if mutant.is_inviable() {
+            lethal += 1;
+        } else if mutant.phenotype == original.phenotype {
+            neutral += 1;
+        } else {
+            phenotype_changing += 1;
+        }
+    }
+
+    println!("Mutants: {}", mutants);
+    print_fraction("Unchanged genome", unchanged, mutants);
+    print_fraction("Neutral", neutral, mutants);
+    print_fraction("Phenotype-changing", phenotype_changing, mutants);
+    print_fraction("Lethal", lethal, mutants);
+    print_fraction(
+        "Robustness (neutral mutated genomes)",
+        neutral,
+        mutants - unchanged,
+    );
+}
+
+fn run_arena(dna: &DNA, iterations: usize) -> usize {
+    let mut cell_manager = CellManager::new();
+    cell_manager.init_with_genomes(vec![dna.clone(); STARTING_CELLS]);
+
+    for _ in 0..iterations {
+        cell_manager.update();
+        if cell_manager.get_cells().is_empty() {
+            return 0;
+        }
+    }
+
+    cell_manager.get_cells().len()
+}
+
+fn run_replicates(dna: &DNA, iterations: usize, replicates: usize) -> String {
+    let populations: Vec<f32> = (0..replicates)
+        .map(|_| run_arena(dna, iterations) as f32)
+        .collect();
+    let extinct = populations
+        .iter()
+        .filter(|&&population| population == 0.0)
+        .count();
+    let mean = populations.iter().sum::<f32>() / replicates as f32;
+    let variance = populations
+        .iter()
+        .map(|population| (population - mean).powi(2))
+        .sum::<f32>()
+        / replicates as f32;
+
+    format!(
+        "{:.1} ± {:.1} cells, {}/{} extinct",
+        mean,
+        variance.sqrt(),
+        extinct,
+        replicates
+    )
+}
+
+pub fn knockout(args: &[String]) {
+    let iterations = parse_count(args.get(1), DEFAULT_KNOCKOUT_ITERATIONS);
+    let replicates = parse_count(args.get(2), DEFAULT_KNOCKOUT_REPLICATES);
+    let (Some(iterations), Some(replicates), 1..=3) = (iterations, replicates, args.len()) else {
+        println!("Usage: CellSimulation knockout <genome.json> [iterations] [replicates]");
+        return;
+    };
+    let replicates = replicates.max(1);
+
+    let Some(dna) = load_genome(&args[0]) else {
+        return;
+    };
+
+    println!(
+        "wild type: {}",
+        run_replicates(&dna, iterations, replicates)
+    );
+    for (codon_index, codon) in dna.get_codons().iter().enumerate() {
+        println!(
+            "{:>5} {}: removed {}, disabled {}",
+            codon_index,
+            format_codon(*codon),
+            run_replicates(&dna.without_codon(codon_index), iterations, replicates),
+            run_replicates(&dna.with_inert_codon(codon_index), iterations, replicates)
+        );
+    }
+}
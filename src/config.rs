@@ -1,3 +1,95 @@
+#[allow(dead_code)]
+pub enum SenescenceModel {
+    None,
+    MetabolicCost,
+    MutationRate,
+}
+
+#[allow(dead_code)]
+pub enum GenomeSeeding {
+    Random(usize),
+    Library(&'static str),
+    Ancestor(&'static str),
+}
+
+#[allow(dead_code)]
+pub enum CodonCostModel {
+    All,
+    Expressed,
+    Regulatory,
+}
+
+#[allow(dead_code)]
+pub enum EpigeneticInheritance {
+    ParentForces,
+    SenseAtBirth,
+    DecayingAverage,
+}
+
+#[allow(dead_code)]
+pub enum MovementModel {
+    Direct,
+    Inertial,
+}
+
+#[allow(dead_code)]
+pub enum BoundaryMode {
+    Cancel,
+    Wrap,
+    Reflect,
+    Clamp,
+}
+
+#[allow(dead_code)]
+pub enum ForceLaw {
+    InverseSquare,
+    InverseLinear,
+    Gaussian,
+    LinearToCutoff,
+    Constant,
+}
+
+#[allow(dead_code)]
+pub enum OffspringPlacement {
+    RandomDirection,
+    GeneControlled,
+    FreeSpace,
+}
+
+#[allow(dead_code)]
+pub enum EmissionModel {
+    Pairwise,
+    ChemicalField,
+}
+
+#[allow(dead_code)]
+pub enum FoodSpawner {
+    Uniform,
+    Patches {
+        count: usize,
+        radius: f32,
+        seed: u64,
+    },
+    Gradient {
+        angle: f32,
+        min_fertility: f32,
+    },
+    PerlinNoise {
+        scale: f64,
+        seed: u32,
+    },
+    FertilityMap(&'static str),
+}
+
+pub struct Nutrient {
+    pub force: u16,
+    pub value: f32,
+    pub spawn_weight: f32,
+    pub max_food: usize,
+    pub spawner: FoodSpawner,
+    pub default_efficiency: f32,
+}
+
 pub const STATE_PATH: &str = "state";
 
 #[allow(dead_code)]
@@ -12,39 +104,104 @@ pub const PRINT_DETAILS_AFTER_FRAMES: usize = 1000;
 
 pub const GAME_SIZE: usize = 2000;
 pub const GRID_CELL_SIZE: usize = 100;
+pub const BOUNDARY_MODE: BoundaryMode = BoundaryMode::Cancel;
 
 pub const STARTING_CELLS: usize = 1000;
 pub const STARTING_FOOD: usize = 2000;
+pub const GENOME_SEEDING: GenomeSeeding = GenomeSeeding::Random(1);
 
 pub const DEFAULT_FOOD_VALUE: f32 = 25.0;
 pub const DEFAULT_CELL_FOOD_VALUE: f32 = 20.0;
 pub const FOOD_ADDED_PER_FRAME: usize = 10;
 pub const MAX_FOOD: usize = 2000;
 
+pub const MOVEMENT_MODEL: MovementModel = MovementModel::Direct;
+pub const TIME_STEP: f32 = 1.0;
+pub const DRAG: f32 = 0.1;
+pub const MASS_PER_SIZE_UNIT: f32 = 0.1;
+pub const MIN_MASS: f32 = 0.1;
+pub const CELL_COLLISIONS: bool = false;
+pub const COLLISION_ITERATIONS: usize = 2;
+
 pub const FORCE_MAX_RANGE_SQ: f32 = 10000.0;
+pub const FORCE_LAW: ForceLaw = ForceLaw::InverseLinear;
+pub const FORCE_SOFTENING: f32 = 0.3;
+pub const FORCE_LAW_SCALE: f32 = 50.0;
+pub const FORCE_STRENGTH: f32 = 1.0;
+pub const EMISSION_MODEL: EmissionModel = EmissionModel::Pairwise;
+pub const CHEMICAL_FIELD_CELL_SIZE: usize = 20;
+pub const CHEMICAL_DEPOSIT_RATE: f32 = 1.0;
+pub const CHEMICAL_DIFFUSION_RATE: f32 = 0.1;
+pub const CHEMICAL_DECAY_RATE: f32 = 0.05;
+pub const CHEMICAL_GRADIENT_STRENGTH: f32 = 10.0;
 pub const FOOD_FORCE: u16 = 0;
 pub const TOXIN_FORCE: u16 = 1;
 
+pub const NUTRIENTS: &[Nutrient] = &[Nutrient {
+    force: FOOD_FORCE,
+    value: DEFAULT_FOOD_VALUE,
+    spawn_weight: 1.0,
+    max_food: MAX_FOOD,
+    spawner: FoodSpawner::Uniform,
+    default_efficiency: 1.0,
+}];
+pub const CORPSE_NUTRIENT: usize = 0;
+pub const FERTILITY_MAP_CELL_SIZE: usize = 10;
+pub const MAX_DIGESTION_EFFICIENCY: f32 = 1.0;
+
 pub const CELL_STARTING_FOOD: f32 = 50.0;
 pub const MAX_CELLS: usize = 2000;
 pub const MIN_FOOD_TO_REPLICATE_RATIO: f32 = 1.1;
 pub const MAX_TOXIN_FORCE: f32 = 5.0;
-pub const MAX_LIFESPAN: usize = 5000;
+pub const TOXIN_FALLOFF_EXPONENT: f32 = 1.0;
+pub const TOXIN_FOOD_TRANSFER: f32 = 0.0;
+pub const MAX_LIFESPAN: usize = 20000;
+pub const SENESCENCE_MODEL: SenescenceModel = SenescenceModel::None;
+pub const SENESCENCE_RATE: f32 = 0.0002;
+pub const PREDATION_SIZE_RATIO: f32 = 1.5;
+pub const OFFSPRING_PLACEMENT: OffspringPlacement = OffspringPlacement::RandomDirection;
+pub const OFFSPRING_DISTANCE: f32 = 2.0;
+pub const OFFSPRING_PLACEMENT_ATTEMPTS: usize = 8;
+pub const MAX_DISPERSAL_DISTANCE: f32 = 50.0;
 
+pub const DISABLED_PRIMARY_BASES: &[u8] = &[];
 pub const FUDGED_MUTATION_RATE_CHANCE: f32 = 0.00001;
 pub const DEFAULT_MUTATION_RATE: f32 = 0.01;
 pub const DEFAULT_PRIMARY_MUTATION_RATE: f32 = 0.001;
 pub const DEFAULT_SECONDARY_MUTATION_RATE: f32 = 0.01;
 pub const DEFAULT_ADD_CODON_MUTATION_RATE: f32 = 0.001;
 pub const DEFAULT_REMOVE_CODON_MUTATION_RATE: f32 = 0.001;
+pub const DEFAULT_DUPLICATION_MUTATION_RATE: f32 = 0.0005;
+pub const DEFAULT_INVERSION_MUTATION_RATE: f32 = 0.0005;
+pub const DEFAULT_TRANSLOCATION_MUTATION_RATE: f32 = 0.0005;
+pub const MAX_STRUCTURAL_MUTATION_LENGTH: usize = 5;
+pub const MAX_TRANSPOSON_LENGTH: usize = 5;
+pub const TRANSPOSON_COPY_CHANCE: f32 = 0.5;
+pub const MIN_GENOME_LENGTH: usize = 1;
+pub const MAX_GENOME_LENGTH: usize = 1000;
+pub const DEFAULT_MUTATION_STEP_SIZE: f32 = 1.0;
+pub const MAX_MUTATION_STEP_SIZE: f32 = 10.0;
 pub const DEFAULT_FOOD_TO_REPLICATE: f32 = 60.0;
 pub const DEFAULT_CELL_SIZE_SQ: f32 = 16.0;
+pub const DEFAULT_SENSING_RANGE_SQ: f32 = FORCE_MAX_RANGE_SQ;
+pub const DEFAULT_LIFESPAN: f32 = 5000.0;
+pub const DEFAULT_DISPERSAL_DISTANCE: f32 = OFFSPRING_DISTANCE;
 
 pub const FOOD_USED_PER_FRAME: f32 = 0.2;
 pub const FOOD_STOLEN_PER_TOXIN_UNIT: f32 = 5.0;
 pub const FOOD_USED_PER_UNIT_MOVED: f32 = 0.02;
+pub const FOOD_USED_PER_UNIT_IMPULSE: f32 = 0.02;
 pub const FOOD_USED_PER_SIZE_UNIT: f32 = 0.05;
+pub const FOOD_USED_PER_SENSING_RANGE_UNIT: f32 = 0.0;
 pub const FOOD_USED_PER_FORCE_EMITTED: f32 = 0.001;
 pub const FOOD_USED_PER_TOXIN_UNIT_EMITTED: f32 = 0.002;
+pub const FOOD_USED_PER_PREDATION_UNIT: f32 = 0.1;
+pub const FOOD_USED_PER_TOXIN_RESISTANCE_UNIT: f32 = 0.1;
+pub const FOOD_USED_PER_DIGESTION_UNIT: f32 = 0.0;
 pub const FOOD_RETENTION_FROM_REPLICATION: f32 = 0.5;
+pub const FOOD_USED_PER_UNIT_DISPERSED: f32 = 0.1;
 pub const FOOD_USED_PER_CODON: f32 = 0.0001;
+pub const CODON_COST_MODEL: CodonCostModel = CodonCostModel::All;
+
+pub const EPIGENETIC_INHERITANCE: EpigeneticInheritance = EpigeneticInheritance::ParentForces;
+pub const EPIGENETIC_DECAY: f32 = 0.9;
@@ -0,0 +1,166 @@
+use std::fmt;
+
+use crate::codons::{format_codon, Codon};
+use crate::dna::DNA;
+
+const MATCH_SCORE: i32 = 2;
+const SAME_PRIMARY_BASE_SCORE: i32 = 1;
+const MISMATCH_SCORE: i32 = -1;
+const GAP_SCORE: i32 = -1;
+
+pub enum CodonDiff {
+    Unchanged {
+        from: usize,
+        to: usize,
+        codon: Codon,
+    },
+    ValueChanged {
+        from: usize,
+        to: usize,
+        before: Codon,
+        after: Codon,
+    },
+    Substituted {
+        from: usize,
+        to: usize,
+        before: Codon,
+        after: Codon,
+    },
+    Inserted {
+        to: usize,
+        codon: Codon,
+    },
+    Deleted {
+        from: usize,
+        codon: Codon,
+    },
+}
+
+impl fmt::Display for CodonDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CodonDiff::Unchanged { from, to, codon } => {
+                write!(f, "  {:>5} {:>5}  {}", from, to, format_codon(*codon))
+            }
+            CodonDiff::ValueChanged {
+                from,
+                to,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ {:>5} {:>5}  {} -> {:.4} ({:+.4})",
+                from,
+                to,
+                format_codon(*before),
+                after.2,
+                after.2 - before.2
+            ),
+            CodonDiff::Substituted {
+                from,
+                to,
+                before,
+                after,
+            } => write!(
+                f,
+                "* {:>5} {:>5}  {} -> {}",
+                from,
+                to,
+                format_codon(*before),
+                format_codon(*after)
+            ),
+            CodonDiff::Inserted { to, codon } => {
+                write!(f, "+ {:>5} {:>5}  {}", "-", to, format_codon(*codon))
+            }
+            CodonDiff::Deleted { from, codon } => {
+                write!(f, "- {:>5} {:>5}  {}", from, "-", format_codon(*codon))
+            }
+        }
+    }
+}
+
+fn alignment_score(a: Codon, b: Codon) -> i32 {
+    if a.0 == b.0 && a.1 == b.1 {
+        MATCH_SCORE
+    } else if a.0 == b.0 {
+        SAME_PRIMARY_BASE_SCORE
+    } else {
+        MISMATCH_SCORE
+    }
+}
+
+impl DNA {
+    pub fn diff(&self, other: &DNA) -> Vec<CodonDiff> {
+        let a = self.get_codons();
+        let b = other.get_codons();
+        let columns = b.len() + 1;
+
+        let mut scores = vec![0; (a.len() + 1) * columns];
+        for i in 0..=a.len() {
+            scores[i * columns] = i as i32 * GAP_SCORE;
+        }
+        for (j, score) in scores.iter_mut().enumerate().take(columns) {
+            *score = j as i32 * GAP_SCORE;
+        }
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let aligned =
+                    scores[(i - 1) * columns + j - 1] + alignment_score(a[i - 1], b[j - 1]);
+                let deleted = scores[(i - 1) * columns + j] + GAP_SCORE;
+                let inserted = scores[i * columns + j - 1] + GAP_SCORE;
+                scores[i * columns + j] = aligned.max(deleted).max(inserted);
+            }
+        }
+
+        let mut diffs = Vec::with_capacity(a.len().max(b.len()));
+        let (mut i, mut j) = (a.len(), b.len());
+        while i > 0 || j > 0 {
+            let score = scores[i * columns + j];
+            if i > 0
+                && j > 0
+                && score == scores[(i - 1) * columns + j - 1] + alignment_score(a[i - 1], b[j - 1])
+            {
+                let (before, after) = (a[i - 1], b[j - 1]);
+                let (from, to) = (i - 1, j - 1);
+                diffs.push(if before.0 != after.0 || before.1 != after.1 {
+                    CodonDiff::Substituted {
+                        from,
+                        to,
+                        before,
+                        after,
+                    }
+                } else if before.2 != after.2 {
+                    CodonDiff::ValueChanged {
+                        from,
+                        to,
+                        before,
+                        after,
+                    }
+                } else {
+                    CodonDiff::Unchanged {
+                        from,
+                        to,
+                        codon: before,
+                    }
+                });
+                i -= 1;
+                j -= 1;
+            } else if i > 0 && score == scores[(i - 1) * columns + j] + GAP_SCORE {
+                diffs.push(CodonDiff::Deleted {
+                    from: i - 1,
+                    codon: a[i - 1],
+                });
+                i -= 1;
+            } else {
+                diffs.push(CodonDiff::Inserted {
+                    to: j - 1,
+                    codon: b[j - 1],
+                });
+                j -= 1;
+            }
+        }
+
+        diffs.reverse();
+        diffs
+    }
+}
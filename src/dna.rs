@@ -1,305 +1,259 @@
 use rand::rngs::ThreadRng;
+use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng};
+use rand_distr::StandardNormal;
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
+use crate::codons::{
+    get_codon_type, random_primary_base, Codon, MutationRates, Phenotype, Regulation,
+    INERT_PRIMARY_BASE,
+};
 use crate::config::*;
 
-enum PrimaryBases {
-    Attraction = 0,
-    Emission = 1,
-    DisableCodon = 2,
-    GlobalMutationRate = 3,
-    IndividualMutationRate = 4,
-    PrimaryMutationRate = 5,
-    SecondaryMutationRate = 6,
-    AddCodonMutationRate = 7,
-    RemoveCodonMutationRate = 8,
-    ReplicationFood = 9,
-    CellSize = 10,
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum CodonExpression {
+    Active,
+    Disabled,
+    Overridden,
+    NoOp,
 }
 
-impl From<u8> for PrimaryBases {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => PrimaryBases::Attraction,
-            1 => PrimaryBases::Emission,
-            2 => PrimaryBases::DisableCodon,
-            3 => PrimaryBases::GlobalMutationRate,
-            4 => PrimaryBases::IndividualMutationRate,
-            5 => PrimaryBases::PrimaryMutationRate,
-            6 => PrimaryBases::SecondaryMutationRate,
-            7 => PrimaryBases::AddCodonMutationRate,
-            8 => PrimaryBases::RemoveCodonMutationRate,
-            9 => PrimaryBases::ReplicationFood,
-            10 => PrimaryBases::CellSize,
-            _ => panic!("Invalid value for PrimaryBases"),
-        }
-    }
-}
-
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
-pub struct DNA(Vec<(u8, u16, f32)>);
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DNA(Vec<Codon>);
 
 impl DNA {
-    pub fn new() -> Self {
+    pub fn random(length: usize) -> Self {
         let mut rng = thread_rng();
-        let mut dna = Self(Vec::new());
-        dna.0.push(dna.random_codon(&mut rng));
+        let mut dna = Self(Vec::with_capacity(length));
+        for _ in 0..length {
+            dna.0.push(dna.random_codon(&mut rng));
+        }
         dna
     }
 
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let buffer = std::fs::read(path)?;
+        let mut dna: Self = serde_json::from_slice(&buffer)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        dna.fix_broken_codons();
+        Ok(dna)
+    }
+
+    pub fn load_library(path: &str) -> std::io::Result<Vec<Self>> {
+        let buffer = std::fs::read(path)?;
+        let mut library: Vec<Self> = serde_json::from_slice(&buffer)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        if library.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("genome library {path} is empty"),
+            ));
+        }
+        library.iter_mut().for_each(Self::fix_broken_codons);
+        Ok(library)
+    }
+
+    pub fn seed_genomes(count: usize) -> Vec<Self> {
+        match GENOME_SEEDING {
+            GenomeSeeding::Random(length) => (0..count).map(|_| Self::random(length)).collect(),
+            GenomeSeeding::Library(path) => {
+                let mut rng = thread_rng();
+                let library = Self::load_library(path)
+                    .unwrap_or_else(|error| panic!("Could not load genome library: {error}"));
+                (0..count)
+                    .map(|_| library.choose(&mut rng).unwrap().clone())
+                    .collect()
+            }
+            GenomeSeeding::Ancestor(path) => {
+                let ancestor = Self::load(path)
+                    .unwrap_or_else(|error| panic!("Could not load ancestor genome: {error}"));
+                vec![ancestor; count]
+            }
+        }
+    }
+
+    pub fn get_codons(&self) -> &[Codon] {
+        &self.0
+    }
+
     pub fn get_codon_count(&self) -> usize {
         self.0.len()
     }
 
+    pub fn without_codon(&self, codon_index: usize) -> Self {
+        let mut dna = self.clone();
+        dna.0.remove(codon_index);
+        dna
+    }
+
+    pub fn with_inert_codon(&self, codon_index: usize) -> Self {
+        let mut dna = self.clone();
+        dna.0[codon_index].0 = INERT_PRIMARY_BASE;
+        dna
+    }
+
+    pub fn get_regulatory_codon_count(&self) -> usize {
+        (0..self.0.len())
+            .filter(|&codon_index| self.get_regulation(codon_index).is_some())
+            .count()
+    }
+
+    fn get_regulation(&self, codon_index: usize) -> Option<Regulation> {
+        get_codon_type(self.0[codon_index].0).and_then(|codon_type| codon_type.regulation())
+    }
+
+    fn is_regulator_triggered(
+        &self,
+        codon_index: usize,
+        regulation: Regulation,
+        initial_forces: &FxHashMap<u16, f32>,
+    ) -> bool {
+        let (_, force, threshold) = self.0[codon_index];
+        let force = *initial_forces.get(&force).unwrap_or(&0.0);
+        if regulation.below_threshold {
+            force < threshold
+        } else {
+            force >= threshold
+        }
+    }
+
+    fn get_regulated_range(
+        &self,
+        codon_index: usize,
+        regulation: Regulation,
+    ) -> std::ops::Range<usize> {
+        let start = (codon_index + 1).min(self.0.len());
+        if regulation.range {
+            let end = (start..self.0.len())
+                .find(|&index| self.get_regulation(index).is_some())
+                .unwrap_or(self.0.len());
+            start..end
+        } else {
+            start..(start + 1).min(self.0.len())
+        }
+    }
+
     pub fn get_activated_codons(&self, initial_forces: &FxHashMap<u16, f32>) -> Vec<usize> {
-        let mut activated_codons = Vec::with_capacity(self.0.len());
+        let mut activated = vec![true; self.0.len()];
+
+        // Codons regulated by an enable codon are only expressed when it triggers.
         for codon_index in 0..self.0.len() {
-            activated_codons.push(codon_index);
+            if let Some(regulation) = self.get_regulation(codon_index) {
+                if regulation.enable {
+                    for regulated_index in self.get_regulated_range(codon_index, regulation) {
+                        activated[regulated_index] = false;
+                    }
+                }
+            }
         }
 
         for codon_index in 0..self.0.len() {
-            if activated_codons.contains(&codon_index) {
-                if self.0[codon_index].0 == PrimaryBases::DisableCodon as u8
-                    && *initial_forces.get(&self.0[codon_index].1).unwrap_or(&0.0)
-                        >= self.0[codon_index].2
-                {
-                    activated_codons.retain(|&x| x != codon_index + 1);
-                }
+            if !activated[codon_index] {
+                continue;
+            }
+
+            let Some(regulation) = self.get_regulation(codon_index) else {
+                continue;
+            };
+            if !self.is_regulator_triggered(codon_index, regulation, initial_forces) {
+                continue;
+            }
+
+            for regulated_index in self.get_regulated_range(codon_index, regulation) {
+                activated[regulated_index] = regulation.enable;
             }
         }
 
-        activated_codons
+        (0..self.0.len())
+            .filter(|&codon_index| activated[codon_index])
+            .collect()
     }
 
     pub fn get_disabled_codons(&self, initial_forces: &FxHashMap<u16, f32>) -> Vec<(usize, bool)> {
         let mut disabled_codons = Vec::new();
         for codon_index in 0..self.0.len() {
-            if self.0[codon_index].0 == PrimaryBases::DisableCodon as u8 {
-                if *initial_forces.get(&self.0[codon_index].1).unwrap_or(&0.0)
-                    >= self.0[codon_index].2
-                {
-                    disabled_codons.push((codon_index, true));
-                } else {
-                    disabled_codons.push((codon_index, false));
-                }
+            if let Some(regulation) = self.get_regulation(codon_index) {
+                // Enable codons keep their targets disabled unless they are triggered.
+                let triggered =
+                    self.is_regulator_triggered(codon_index, regulation, initial_forces);
+                disabled_codons.push((codon_index, triggered != regulation.enable));
             }
         }
 
         disabled_codons
     }
 
-    pub fn process_dna(
+    pub fn get_codon_expression(
         &self,
-        activated_codons: &[usize],
-    ) -> (FxHashMap<u16, f32>, Vec<(u16, f32)>, f32, f32) {
-        let mut attractions = FxHashMap::default();
-        let mut emissions: Vec<(u16, f32)> = Vec::with_capacity(self.0.len());
-        let mut food_to_replicate = DEFAULT_FOOD_TO_REPLICATE;
-        let mut size = DEFAULT_CELL_SIZE_SQ;
-
-        for codon_index in activated_codons {
-            let codon_index = *codon_index;
-            match PrimaryBases::from(self.0[codon_index].0) {
-                PrimaryBases::Attraction => {
-                    let entry = attractions.entry(self.0[codon_index].1).or_insert(0.0);
-                    *entry += self.0[codon_index].2;
-                }
-                PrimaryBases::Emission => {
-                    if let Some(emission) = emissions
-                        .iter_mut()
-                        .find(|x| (**x).0 == self.0[codon_index].1)
-                    {
-                        emission.1 += self.0[codon_index].2;
-                    } else {
-                        emissions.push((self.0[codon_index].1, self.0[codon_index].2));
-                    }
-                }
-                PrimaryBases::ReplicationFood => food_to_replicate = self.0[codon_index].2,
-                PrimaryBases::CellSize => size = self.0[codon_index].2,
-                _ => (),
-            }
-        }
+        initial_forces: &FxHashMap<u16, f32>,
+    ) -> Vec<CodonExpression> {
+        let mut expression = vec![CodonExpression::Disabled; self.0.len()];
+        let mut overriding_keys = FxHashSet::default();
 
-        (attractions, emissions, food_to_replicate, size)
-    }
+        for codon_index in self.get_activated_codons(initial_forces).into_iter().rev() {
+            let (primary_base, secondary_base, _) = self.0[codon_index];
+            let Some(codon_type) = get_codon_type(primary_base) else {
+                expression[codon_index] = CodonExpression::NoOp;
+                continue;
+            };
 
-    fn get_mutation_rates(
-        &self,
-        rng: &mut ThreadRng,
-        activated_codons: &[usize],
-    ) -> (f32, FxHashMap<usize, f32>, f32, f32, f32, f32) {
-        let mut global_mutation_rate = DEFAULT_MUTATION_RATE;
-        let mut individual_mutation_rates = FxHashMap::default();
-        let mut primary_mutation_rate = DEFAULT_PRIMARY_MUTATION_RATE;
-        let mut secondary_mutation_rate = DEFAULT_SECONDARY_MUTATION_RATE;
-        let mut add_codon_mutation_rate = DEFAULT_ADD_CODON_MUTATION_RATE;
-        let mut remove_codon_mutation_rate = DEFAULT_REMOVE_CODON_MUTATION_RATE;
-
-        if rng.gen_range(0.0..=1.0) > FUDGED_MUTATION_RATE_CHANCE {
-            for codon_index in activated_codons {
-                let codon_index = *codon_index;
-                match PrimaryBases::from(self.0[codon_index].0) {
-                    PrimaryBases::GlobalMutationRate => {
-                        global_mutation_rate = self.0[codon_index].2
-                    }
-                    PrimaryBases::IndividualMutationRate => {
-                        individual_mutation_rates
-                            .insert(self.0[codon_index].1 as usize, self.0[codon_index].2);
-                    }
-                    PrimaryBases::PrimaryMutationRate => {
-                        primary_mutation_rate = self.0[codon_index].2
-                    }
-                    PrimaryBases::SecondaryMutationRate => {
-                        secondary_mutation_rate = self.0[codon_index].2
-                    }
-                    PrimaryBases::AddCodonMutationRate => {
-                        add_codon_mutation_rate = self.0[codon_index].2
-                    }
-                    PrimaryBases::RemoveCodonMutationRate => {
-                        remove_codon_mutation_rate = self.0[codon_index].2
-                    }
-                    _ => (),
+            expression[codon_index] = if let Some(regulation) = codon_type.regulation() {
+                if (regulation.enable
+                    || self.is_regulator_triggered(codon_index, regulation, initial_forces))
+                    && !self.get_regulated_range(codon_index, regulation).is_empty()
+                {
+                    CodonExpression::Active
+                } else {
+                    CodonExpression::NoOp
                 }
-            }
+            } else if let Some(key) = codon_type.override_key(secondary_base) {
+                if overriding_keys.insert((primary_base, key)) {
+                    CodonExpression::Active
+                } else {
+                    CodonExpression::Overridden
+                }
+            } else {
+                CodonExpression::Active
+            };
         }
 
-        (
-            global_mutation_rate,
-            individual_mutation_rates,
-            primary_mutation_rate,
-            secondary_mutation_rate,
-            add_codon_mutation_rate,
-            remove_codon_mutation_rate,
-        )
+        expression
     }
 
-    pub fn get_mutation_rates_no_rng(&self) -> (f32, FxHashMap<usize, f32>, f32, f32, f32, f32) {
-        let mut global_mutation_rate = DEFAULT_MUTATION_RATE;
-        let mut individual_mutation_rates = FxHashMap::default();
-        let mut primary_mutation_rate = DEFAULT_PRIMARY_MUTATION_RATE;
-        let mut secondary_mutation_rate = DEFAULT_SECONDARY_MUTATION_RATE;
-        let mut add_codon_mutation_rate = DEFAULT_ADD_CODON_MUTATION_RATE;
-        let mut remove_codon_mutation_rate = DEFAULT_REMOVE_CODON_MUTATION_RATE;
+    pub fn process_dna(&self, activated_codons: &[usize]) -> Phenotype {
+        let mut phenotype = Phenotype::default();
 
-        for codon_index in 0..self.0.len() {
-            match PrimaryBases::from(self.0[codon_index].0) {
-                PrimaryBases::GlobalMutationRate => global_mutation_rate = self.0[codon_index].2,
-                PrimaryBases::IndividualMutationRate => {
-                    individual_mutation_rates
-                        .insert(self.0[codon_index].1 as usize, self.0[codon_index].2);
-                }
-                PrimaryBases::PrimaryMutationRate => primary_mutation_rate = self.0[codon_index].2,
-                PrimaryBases::SecondaryMutationRate => {
-                    secondary_mutation_rate = self.0[codon_index].2
-                }
-                PrimaryBases::AddCodonMutationRate => {
-                    add_codon_mutation_rate = self.0[codon_index].2
-                }
-                PrimaryBases::RemoveCodonMutationRate => {
-                    remove_codon_mutation_rate = self.0[codon_index].2
-                }
-                _ => (),
+        for codon_index in activated_codons {
+            let (primary_base, secondary_base, value) = self.0[*codon_index];
+            if let Some(codon_type) = get_codon_type(primary_base) {
+                codon_type.express(secondary_base, value, &mut phenotype);
             }
         }
 
-        (
-            global_mutation_rate,
-            individual_mutation_rates,
-            primary_mutation_rate,
-            secondary_mutation_rate,
-            add_codon_mutation_rate,
-            remove_codon_mutation_rate,
-        )
+        phenotype
     }
 
     fn fix_broken_codon(&mut self, codon_index: usize) {
-        match PrimaryBases::from(self.0[codon_index].0) {
-            PrimaryBases::Emission => {
-                if self.0[codon_index].1 == FOOD_FORCE {
-                    self.0[codon_index].1 = TOXIN_FORCE;
-                }
-
-                if self.0[codon_index].1 == TOXIN_FORCE && self.0[codon_index].2 > MAX_TOXIN_FORCE {
-                    self.0[codon_index].2 = MAX_TOXIN_FORCE;
-                }
-
-                if self.0[codon_index].2 < 0.0 {
-                    self.0[codon_index].2 = 0.0;
-                }
-            }
-            PrimaryBases::ReplicationFood => {
-                self.0[codon_index].2 = self.0[codon_index]
-                    .2
-                    .max(CELL_STARTING_FOOD * MIN_FOOD_TO_REPLICATE_RATIO)
-            }
-            PrimaryBases::DisableCodon | PrimaryBases::CellSize => {
-                self.0[codon_index].2 = self.0[codon_index].2.max(0.0)
-            }
-            PrimaryBases::GlobalMutationRate
-            | PrimaryBases::IndividualMutationRate
-            | PrimaryBases::PrimaryMutationRate
-            | PrimaryBases::SecondaryMutationRate
-            | PrimaryBases::AddCodonMutationRate
-            | PrimaryBases::RemoveCodonMutationRate => {
-                self.0[codon_index].2 = self.0[codon_index].2.max(0.0).min(1.0)
-            }
-            _ => (),
-        };
+        let (primary_base, secondary_base, value) = &mut self.0[codon_index];
+        if let Some(codon_type) = get_codon_type(*primary_base) {
+            codon_type.fix(secondary_base, value);
+        }
     }
 
-    fn random_codon(&self, rng: &mut ThreadRng) -> (u8, u16, f32) {
-        let primary_base = rng.gen_range(0..=10);
-        if primary_base == PrimaryBases::Attraction as u8 {
-            (
-                primary_base,
-                rng.gen_range(0..=10),
-                rng.gen_range(-10.0..=10.0),
-            )
-        } else if primary_base == PrimaryBases::Emission as u8 {
-            let secondary_force = rng.gen_range(0..=10);
-            if secondary_force == FOOD_FORCE {
-                (
-                    primary_base,
-                    TOXIN_FORCE,
-                    rng.gen_range(0.0..=MAX_TOXIN_FORCE),
-                )
-            } else if secondary_force == TOXIN_FORCE {
-                (
-                    primary_base,
-                    secondary_force,
-                    rng.gen_range(0.0..=MAX_TOXIN_FORCE),
-                )
-            } else {
-                (primary_base, secondary_force, rng.gen_range(-10.0..=10.0))
-            }
-        } else if primary_base == PrimaryBases::ReplicationFood as u8 {
-            (
-                primary_base,
-                0,
-                rng.gen_range(
-                    CELL_STARTING_FOOD * MIN_FOOD_TO_REPLICATE_RATIO
-                        ..=CELL_STARTING_FOOD * MIN_FOOD_TO_REPLICATE_RATIO * 2.0,
-                ),
-            )
-        } else if primary_base == PrimaryBases::DisableCodon as u8 {
-            (
-                primary_base,
-                rng.gen_range(0..=10) as u16,
-                rng.gen_range(0.0..=100.0),
-            )
-        } else if primary_base == PrimaryBases::CellSize as u8 {
-            (
-                primary_base,
-                0,
-                rng.gen_range(0.1..=DEFAULT_CELL_SIZE_SQ * 2.0),
-            )
-        } else {
-            (primary_base, 0, rng.gen_range(0.0..=1.0))
+    fn fix_broken_codons(&mut self) {
+        for codon_index in 0..self.0.len() {
+            self.fix_broken_codon(codon_index);
         }
     }
 
+    fn random_codon(&self, rng: &mut ThreadRng) -> Codon {
+        let primary_base = random_primary_base(rng);
+        let (secondary_base, value) = get_codon_type(primary_base).unwrap().random(rng);
+        (primary_base, secondary_base, value)
+    }
+
     fn frameshift_mutation(
         &mut self,
         rng: &mut ThreadRng,
@@ -308,50 +262,213 @@ impl DNA {
     ) {
         let r = rng.gen_range(0.0..=1.0);
         if r <= add_codon_mutation_rate {
-            let codon_index = rng.gen_range(0..self.0.len());
-            self.0.insert(codon_index, self.random_codon(rng));
-        } else if r <= remove_codon_mutation_rate && self.0.len() > 0 {
+            if self.0.len() < MAX_GENOME_LENGTH {
+                let codon_index = rng.gen_range(0..=self.0.len());
+                self.0.insert(codon_index, self.random_codon(rng));
+            }
+        } else if r <= remove_codon_mutation_rate && self.0.len() > MIN_GENOME_LENGTH {
             let codon_index = rng.gen_range(0..self.0.len());
             self.0.remove(codon_index);
         }
     }
 
-    pub fn mutate(&mut self, activated_codons: &[usize]) {
+    fn random_block(&self, rng: &mut ThreadRng, max_length: usize) -> std::ops::Range<usize> {
+        let start = rng.gen_range(0..self.0.len());
+        let max_length = max_length.min(self.0.len() - start);
+        let length = rng.gen_range(1..=max_length);
+        start..start + length
+    }
+
+    fn structural_mutation(
+        &mut self,
+        rng: &mut ThreadRng,
+        mutation_rates: &MutationRates,
+        mutation_rate_scale: f32,
+    ) {
+        if !self.0.is_empty()
+            && self.0.len() < MAX_GENOME_LENGTH
+            && rng.gen_range(0.0..=1.0)
+                <= mutation_rates.duplication_mutation_rate * mutation_rate_scale
+        {
+            let max_length = MAX_STRUCTURAL_MUTATION_LENGTH.min(MAX_GENOME_LENGTH - self.0.len());
+            let block = self.random_block(rng, max_length);
+            let end = block.end;
+            let duplicate = self.0[block].to_vec();
+            self.0.splice(end..end, duplicate);
+        }
+
+        if !self.0.is_empty()
+            && rng.gen_range(0.0..=1.0)
+                <= mutation_rates.inversion_mutation_rate * mutation_rate_scale
+        {
+            let block = self.random_block(rng, MAX_STRUCTURAL_MUTATION_LENGTH);
+            self.0[block].reverse();
+        }
+
+        if !self.0.is_empty()
+            && rng.gen_range(0.0..=1.0)
+                <= mutation_rates.translocation_mutation_rate * mutation_rate_scale
+        {
+            let block = self.random_block(rng, MAX_STRUCTURAL_MUTATION_LENGTH);
+            let moved: Vec<_> = self.0.drain(block).collect();
+            let codon_index = rng.gen_range(0..=self.0.len());
+            self.0.splice(codon_index..codon_index, moved);
+        }
+    }
+
+    fn transposition(&mut self, rng: &mut ThreadRng, mutation_rate_scale: f32) {
+        let mut events = Vec::new();
+        let mut codon_index = 0;
+        while codon_index < self.0.len() {
+            let (primary_base, carried_codons, rate) = self.0[codon_index];
+            let is_transposon =
+                get_codon_type(primary_base).is_some_and(|codon_type| codon_type.is_transposon());
+            if is_transposon && rng.gen_range(0.0..=1.0) <= rate * mutation_rate_scale {
+                let end = (codon_index + 1 + carried_codons as usize).min(self.0.len());
+                let copy = rng.gen_range(0.0..=1.0) <= TRANSPOSON_COPY_CHANCE;
+                events.push((codon_index..end, copy));
+                codon_index = end;
+            } else {
+                codon_index += 1;
+            }
+        }
+
+        let mut elements = Vec::with_capacity(events.len());
+        for (block, copy) in events.into_iter().rev() {
+            let element = if copy {
+                self.0[block].to_vec()
+            } else {
+                self.0.drain(block).collect()
+            };
+            elements.push((element, copy));
+        }
+
+        for (element, copy) in elements {
+            if copy && self.0.len() + element.len() > MAX_GENOME_LENGTH {
+                continue;
+            }
+            let codon_index = rng.gen_range(0..=self.0.len());
+            self.0.splice(codon_index..codon_index, element);
+        }
+    }
+
+    pub fn mutate(&mut self, mutation_rates: &MutationRates, mutation_rate_scale: f32) {
         let mut rng = thread_rng();
-        let (
-            global_mutation_rate,
-            individual_mutation_rates,
-            primary_mutation_rate,
-            secondary_mutation_rate,
-            add_codon_mutation_rate,
-            remove_codon_mutation_rate,
-        ) = self.get_mutation_rates(&mut rng, activated_codons);
+        let default_mutation_rates;
+        let mutation_rates = if rng.gen_range(0.0..=1.0) > FUDGED_MUTATION_RATE_CHANCE {
+            mutation_rates
+        } else {
+            default_mutation_rates = MutationRates::default();
+            &default_mutation_rates
+        };
 
         for codon_index in 0..self.0.len() {
-            let mutation_rate = individual_mutation_rates
+            let mutation_rate = mutation_rates
+                .individual_mutation_rates
                 .get(&codon_index)
-                .unwrap_or(&global_mutation_rate);
+                .unwrap_or(&mutation_rates.global_mutation_rate);
 
-            if rng.gen_range(0.0..=1.0) > *mutation_rate {
+            if rng.gen_range(0.0..=1.0) > *mutation_rate * mutation_rate_scale {
                 continue;
             }
 
             let mutation_type = rng.gen_range(0.0..=1.0);
-            if mutation_type <= primary_mutation_rate {
-                self.0[codon_index].0 = rng.gen_range(0..=10);
-            } else if mutation_type <= secondary_mutation_rate {
+            if mutation_type <= mutation_rates.primary_mutation_rate {
+                self.0[codon_index].0 = random_primary_base(&mut rng);
+            } else if mutation_type <= mutation_rates.secondary_mutation_rate {
                 self.0[codon_index].1 =
                     (self.0[codon_index].1 as i16 + rng.gen_range(-1..=1)).max(0) as u16;
             } else {
-                self.0[codon_index].2 += rng.gen_range(-1.0..=1.0);
+                let mutation_scale = get_codon_type(self.0[codon_index].0)
+                    .map_or(1.0, |codon_type| codon_type.mutation_scale());
+                let step: f32 = rng.sample(StandardNormal);
+                self.0[codon_index].2 += step * mutation_scale * mutation_rates.mutation_step_size;
             }
             self.fix_broken_codon(codon_index);
         }
 
         self.frameshift_mutation(
             &mut rng,
-            add_codon_mutation_rate,
-            remove_codon_mutation_rate,
+            mutation_rates.add_codon_mutation_rate * mutation_rate_scale,
+            mutation_rates.remove_codon_mutation_rate * mutation_rate_scale,
+        );
+        self.structural_mutation(&mut rng, mutation_rates, mutation_rate_scale);
+        self.transposition(&mut rng, mutation_rate_scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DISABLE_CODON: u8 = 2;
+    const CELL_SIZE: u8 = 10;
+    const ENABLE_CODON: u8 = 11;
+
+    fn forces(force: f32) -> FxHashMap<u16, f32> {
+        FxHashMap::from_iter([(0, force)])
+    }
+
+    #[test]
+    fn enable_codon_gates_its_target() {
+        let dna = DNA(vec![(ENABLE_CODON, 0, 1.0), (CELL_SIZE, 0, 5.0)]);
+
+        assert_eq!(dna.get_activated_codons(&forces(0.0)), vec![0]);
+        assert_eq!(dna.get_activated_codons(&forces(2.0)), vec![0, 1]);
+    }
+
+    #[test]
+    fn disable_codon_disables_its_target() {
+        let dna = DNA(vec![(DISABLE_CODON, 0, 1.0), (CELL_SIZE, 0, 5.0)]);
+
+        assert_eq!(dna.get_activated_codons(&forces(0.0)), vec![0, 1]);
+        assert_eq!(dna.get_activated_codons(&forces(2.0)), vec![0]);
+    }
+
+    #[test]
+    fn disabled_enable_codon_keeps_its_target_disabled() {
+        let dna = DNA(vec![
+            (DISABLE_CODON, 0, 1.0),
+            (ENABLE_CODON, 0, 1.0),
+            (CELL_SIZE, 0, 5.0),
+        ]);
+
+        assert_eq!(dna.get_activated_codons(&forces(2.0)), vec![0]);
+    }
+
+    #[test]
+    fn disabled_codons_report_whether_targets_are_disabled() {
+        let enable = DNA(vec![(ENABLE_CODON, 0, 1.0), (CELL_SIZE, 0, 5.0)]);
+        let disable = DNA(vec![(DISABLE_CODON, 0, 1.0), (CELL_SIZE, 0, 5.0)]);
+
+        assert_eq!(enable.get_disabled_codons(&forces(0.0)), vec![(0, true)]);
+        assert_eq!(enable.get_disabled_codons(&forces(2.0)), vec![(0, false)]);
+        assert_eq!(disable.get_disabled_codons(&forces(0.0)), vec![(0, false)]);
+        assert_eq!(disable.get_disabled_codons(&forces(2.0)), vec![(0, true)]);
+    }
+
+    #[test]
+    fn codon_expression_reports_regulation() {
+        use CodonExpression::*;
+
+        let enable = DNA(vec![(ENABLE_CODON, 0, 1.0), (CELL_SIZE, 0, 5.0)]);
+        let disable = DNA(vec![(DISABLE_CODON, 0, 1.0), (CELL_SIZE, 0, 5.0)]);
+
+        assert_eq!(
+            enable.get_codon_expression(&forces(0.0)),
+            vec![Active, Disabled]
+        );
+        assert_eq!(
+            enable.get_codon_expression(&forces(2.0)),
+            vec![Active, Active]
+        );
+        assert_eq!(
+            disable.get_codon_expression(&forces(0.0)),
+            vec![NoOp, Active]
+        );
+        assert_eq!(
+            disable.get_codon_expression(&forces(2.0)),
+            vec![Active, Disabled]
         );
     }
 }
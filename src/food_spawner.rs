@@ -0,0 +1,126 @@
+use noise::{NoiseFn, Perlin};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::{StdRng, ThreadRng};
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+
+use crate::boundary::confine;
+use crate::config::*;
+
+pub enum Spawner {
+    Uniform,
+    Patches {
+        centers: Vec<(f32, f32)>,
+        radius: f32,
+    },
+    Fertility {
+        cells: WeightedIndex<f32>,
+        cells_per_axis: usize,
+    },
+}
+
+impl Spawner {
+    pub fn new(spawner: &FoodSpawner) -> Self {
+        match *spawner {
+            FoodSpawner::Uniform => Spawner::Uniform,
+            FoodSpawner::Patches {
+                count,
+                radius,
+                seed,
+            } => {
+                // Seeded so the patches stay in place across restarts and reloads.
+                let mut rng = StdRng::seed_from_u64(seed);
+                Spawner::Patches {
+                    centers: (0..count)
+                        .map(|_| {
+                            (
+                                rng.gen_range(0.0..GAME_SIZE as f32),
+                                rng.gen_range(0.0..GAME_SIZE as f32),
+                            )
+                        })
+                        .collect(),
+                    radius,
+                }
+            }
+            FoodSpawner::Gradient {
+                angle,
+                min_fertility,
+            } => {
+                let (dx, dy) = (angle.cos(), angle.sin());
+                let span = dx.abs() + dy.abs();
+                Self::from_fertility(|x, y| {
+                    let progress = ((x - 0.5) * dx + (y - 0.5) * dy) / span + 0.5;
+                    min_fertility + (1.0 - min_fertility) * progress
+                })
+            }
+            FoodSpawner::PerlinNoise { scale, seed } => {
+                let perlin = Perlin::new(seed);
+                Self::from_fertility(|x, y| {
+                    let x = (x * GAME_SIZE as f32) as f64 * scale;
+                    let y = (y * GAME_SIZE as f32) as f64 * scale;
+                    (perlin.get([x, y]) as f32 + 1.0) / 2.0
+                })
+            }
+            FoodSpawner::FertilityMap(path) => {
+                let image = image::open(path)
+                    .unwrap_or_else(|error| panic!("Could not load fertility map {path}: {error}"))
+                    .to_luma8();
+                let (width, height) = image.dimensions();
+                Self::from_fertility(|x, y| {
+                    let pixel_x = ((x * width as f32) as u32).min(width - 1);
+                    let pixel_y = ((y * height as f32) as u32).min(height - 1);
+                    image.get_pixel(pixel_x, pixel_y).0[0] as f32 / 255.0
+                })
+            }
+        }
+    }
+
+    /// Builds a fertility map by evaluating `fertility` at the center of every map cell,
+    /// with coordinates given as fractions of the world size.
+    fn from_fertility(fertility: impl Fn(f32, f32) -> f32) -> Self {
+        let cells_per_axis = GAME_SIZE.div_ceil(FERTILITY_MAP_CELL_SIZE);
+        let mut weights = Vec::with_capacity(cells_per_axis * cells_per_axis);
+        for y in 0..cells_per_axis {
+            for x in 0..cells_per_axis {
+                let x = (x as f32 + 0.5) / cells_per_axis as f32;
+                let y = (y as f32 + 0.5) / cells_per_axis as f32;
+                weights.push(fertility(x, y).clamp(0.0, 1.0));
+            }
+        }
+
+        Spawner::Fertility {
+            cells: WeightedIndex::new(weights).expect("fertility map has no fertile cells"),
+            cells_per_axis,
+        }
+    }
+
+    pub fn get_position(&self, rng: &mut ThreadRng) -> (f32, f32) {
+        match self {
+            Spawner::Uniform => (
+                rng.gen_range(0.0..GAME_SIZE as f32),
+                rng.gen_range(0.0..GAME_SIZE as f32),
+            ),
+            Spawner::Patches { centers, radius } => {
+                let (x, y) = centers[rng.gen_range(0..centers.len())];
+                let offset_x: f32 = rng.sample(StandardNormal);
+                let offset_y: f32 = rng.sample(StandardNormal);
+                (
+                    confine(x + offset_x * radius),
+                    confine(y + offset_y * radius),
+                )
+            }
+            Spawner::Fertility {
+                cells,
+                cells_per_axis,
+            } => {
+                let index = cells.sample(rng);
+                let x = (index % cells_per_axis) as f32 + rng.gen_range(0.0..1.0);
+                let y = (index / cells_per_axis) as f32 + rng.gen_range(0.0..1.0);
+                (
+                    confine(x * FERTILITY_MAP_CELL_SIZE as f32),
+                    confine(y * FERTILITY_MAP_CELL_SIZE as f32),
+                )
+            }
+        }
+    }
+}
@@ -1,7 +1,13 @@
+mod boundary;
 mod cell;
 mod cell_manager;
+mod chemical_field;
+mod codons;
+mod commands;
 mod config;
+mod diff;
 mod dna;
+mod food_spawner;
 mod id;
 
 use config::*;
@@ -12,6 +18,8 @@ mod graphics {
 
     use crate::config::*;
 
+    const NUTRIENT_COLORS: [u32; 4] = [0xFF0000, 0x00FF00, 0x0000FF, 0xFFFF00];
+
     pub struct Graphics {
         window: Window,
         scale_factor_x: f32,
@@ -36,7 +44,11 @@ mod graphics {
             }
         }
 
-        pub fn update(&mut self, cells: &Vec<crate::cell::Cell>, food: &Vec<(f32, f32, f32)>) {
+        pub fn update(
+            &mut self,
+            cells: &Vec<crate::cell::Cell>,
+            food: &Vec<crate::cell_manager::Food>,
+        ) {
             let mut buffer: Vec<u32> = vec![0; WINDOW_WIDTH * WINDOW_HEIGHT];
 
             for cell in cells {
@@ -57,7 +69,7 @@ mod graphics {
                 let x = (x * self.scale_factor_x) as usize;
                 let y = (y * self.scale_factor_y) as usize;
 
-                let color = 0xFF0000;
+                let color = NUTRIENT_COLORS[f.3 % NUTRIENT_COLORS.len()];
                 buffer[y * WINDOW_WIDTH + x] = color;
             }
 
@@ -75,14 +87,14 @@ mod graphics {
 #[derive(serde::Serialize, serde::Deserialize)]
 struct SimulationState {
     cells: Vec<cell::Cell>,
-    food: Vec<(f32, f32, f32)>,
+    food: Vec<cell_manager::Food>,
     iteration: usize,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct SimulationStateJson {
     cells: Vec<cell::ReadableCell>,
-    food: Vec<(f32, f32, f32)>,
+    food: Vec<cell_manager::Food>,
     iteration: usize,
 }
 
@@ -96,11 +108,7 @@ fn save_state(state: &SimulationState, path: &str) -> std::io::Result<()> {
 fn save_state_json(state: &SimulationState, path: &str) -> std::io::Result<()> {
     std::fs::File::create(path)?;
     let state = SimulationStateJson {
-        cells: state
-            .cells
-            .iter()
-            .map(|c| cell::ReadableCell::new(c))
-            .collect(),
+        cells: state.cells.iter().map(cell::ReadableCell::new).collect(),
         food: state.food.clone(),
         iteration: state.iteration,
     };
@@ -111,11 +119,19 @@ fn save_state_json(state: &SimulationState, path: &str) -> std::io::Result<()> {
 
 fn load_state(path: &str) -> std::io::Result<SimulationState> {
     let buffer = std::fs::read(path)?;
-    let state = bincode::deserialize(&buffer).unwrap();
-    Ok(state)
+    bincode::deserialize(&buffer)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
 }
 
 fn main() {
+    let args = std::env::args().collect::<Vec<String>>();
+    match args.get(1).map(String::as_str) {
+        Some("diff") => return commands::diff(&args[2..]),
+        Some("robustness") => return commands::robustness(&args[2..]),
+        Some("knockout") => return commands::knockout(&args[2..]),
+        _ => (),
+    }
+
     #[cfg(feature = "profiling")]
     let guard = pprof::ProfilerGuard::new(10000).unwrap();
 
@@ -124,17 +140,31 @@ fn main() {
 
     let running_clone = running.clone();
     let _ = ctrlc::set_handler(move || {
-        let _ = running_clone.store(false, std::sync::atomic::Ordering::SeqCst);
+        running_clone.store(false, std::sync::atomic::Ordering::SeqCst);
     });
 
     let mut iteration = 0;
-    if let Ok(state) = load_state(STATE_PATH) {
-        println!("Loaded state from file");
-        cell_manager.init_with_starting(state.cells, state.food);
-        iteration = state.iteration;
-    } else {
-        println!("No state file found, starting fresh");
-        cell_manager.init();
+    match load_state(STATE_PATH) {
+        Ok(state) => {
+            println!("Loaded state from file");
+            cell_manager.init_with_starting(state.cells, state.food);
+            iteration = state.iteration;
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            println!("No state file found, starting fresh");
+            cell_manager.init();
+        }
+        Err(error) => {
+            let backup_path = format!("{STATE_PATH}.bak");
+            if let Err(rename_error) = std::fs::rename(STATE_PATH, &backup_path) {
+                eprintln!("Could not load state file ({error}) or move it to {backup_path} ({rename_error})");
+                std::process::exit(1);
+            }
+            println!(
+                "Could not load state file ({error}), moved it to {backup_path} and starting fresh"
+            );
+            cell_manager.init();
+        }
     }
 
     #[cfg(feature = "graphics")]
@@ -185,7 +215,6 @@ fn main() {
     println!("Saving state to file: {}", STATE_PATH);
     save_state(&state, STATE_PATH).unwrap();
 
-    let args = std::env::args().collect::<Vec<String>>();
     if args.len() > 1 {
         println!("Saving JSON state to file: {}", &args[1]);
         save_state_json(&state, &args[1]).unwrap();